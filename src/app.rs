@@ -1,21 +1,34 @@
+use crate::bindings::{self, Signature, TsType, TypeRef, TypeRegistry};
+use crate::capability::Capabilities;
+use crate::blob::Blobs;
+use crate::command::{CommandContext, Commands, InFlight};
+use crate::group::Group;
+use crate::handle::{AppHandle, UserEvent};
+#[cfg(feature = "tokio")]
+use crate::handler::AsyncHandler;
+use crate::handler::Handler;
+use crate::lifecycle::{
+    CloseContext, CloseDecision, CloseHook, ExitContext, ExitHook, PageLoadContext, PageLoadHook, SetupContext, SetupHook,
+};
+use crate::middleware::Next;
+use crate::pool::ThreadPool;
 use crate::routing::Router;
+use crate::state::StateMap;
 use crate::request::{Request, Response};
-use crate::window::{self, IpcRequest, IpcResponse};
+use crate::webview::{Context, Windows, MAIN_WINDOW};
+use crate::window_state::WindowStates;
+use crate::window::{self, IpcResponse, WindowOptions};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Display;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use tao::dpi::LogicalSize;
+use std::sync::Arc;
+use std::time::Duration;
 use tao::event::{Event, WindowEvent};
 use tao::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
-use tao::window::WindowBuilder;
-use wry::{WebViewBuilder, WebContext};
-
-const IPC_BRIDGE_JS: &str = include_str!("ipc_bridge.js");
-
-#[derive(Debug)]
-enum UserEvent {
-    IpcResponse(String),
-    CloseRequested,
-}
+use tao::platform::run_return::EventLoopExtRunReturn;
+use tao::window::Icon;
+use wry::WebContext;
 
 pub struct App {
     title: String,
@@ -24,13 +37,36 @@ pub struct App {
     min_width: f64,
     min_height: f64,
     decorations: bool,
+    always_on_top: bool,
+    resizable: bool,
+    visible: bool,
+    skip_taskbar: bool,
+    visible_on_all_workspaces: bool,
+    icon: Option<Icon>,
+    remember_window_state: bool,
+    workers: usize,
     router: Router,
+    state: StateMap,
+    handle: AppHandle,
+    commands: Commands,
+    capabilities: Capabilities,
+    ipc_timeout: Duration,
+    close_timeout: Duration,
+    setup: Option<SetupHook>,
+    on_page_load: Option<PageLoadHook>,
+    on_close_requested: Option<CloseHook>,
+    on_exit: Option<ExitHook>,
+    types: TypeRegistry,
     frontend_path: Option<String>,
     frontend_embedded: Option<&'static include_dir::Dir<'static>>,
 }
 
 impl App {
     pub fn new(title: impl Into<String>, width: u32, height: u32) -> Self {
+        let handle = AppHandle::new();
+        let mut state = StateMap::default();
+        state.insert(handle.clone());
+
         Self {
             title: title.into(),
             width: width as f64,
@@ -38,7 +74,26 @@ impl App {
             min_width: 400.0,
             min_height: 300.0,
             decorations: false,
+            always_on_top: false,
+            resizable: true,
+            visible: true,
+            skip_taskbar: false,
+            visible_on_all_workspaces: false,
+            icon: None,
+            remember_window_state: false,
+            workers: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
             router: Router::new(),
+            state,
+            handle,
+            commands: Commands::default(),
+            capabilities: Capabilities::default(),
+            ipc_timeout: Duration::from_secs(5),
+            close_timeout: Duration::from_secs(5),
+            setup: None,
+            on_page_load: None,
+            on_close_requested: None,
+            on_exit: None,
+            types: TypeRegistry::default(),
             frontend_path: None,
             frontend_embedded: None,
         }
@@ -55,14 +110,226 @@ impl App {
         self
     }
 
-    pub fn route<F>(mut self, method: &str, path: &str, handler: F) -> Self
+    pub fn always_on_top(mut self, enabled: bool) -> Self {
+        self.always_on_top = enabled;
+        self
+    }
+
+    pub fn resizable(mut self, enabled: bool) -> Self {
+        self.resizable = enabled;
+        self
+    }
+
+    /// Start with windows hidden, e.g. to `show()` them once the page has rendered
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Keep windows out of the taskbar (Windows and Linux)
+    pub fn skip_taskbar(mut self, enabled: bool) -> Self {
+        self.skip_taskbar = enabled;
+        self
+    }
+
+    pub fn visible_on_all_workspaces(mut self, enabled: bool) -> Self {
+        self.visible_on_all_workspaces = enabled;
+        self
+    }
+
+    /// Set the window icon from PNG bytes, e.g. `include_bytes!("../icon.png")`.
+    /// Panics if the PNG can't be decoded.
+    pub fn icon(mut self, png: &[u8]) -> Self {
+        match window::icon_from_png(png) {
+            Ok(icon) => self.icon = Some(icon),
+            Err(e) => panic!("Invalid window icon: {}", e),
+        }
+        self
+    }
+
+    /// Reopen windows with the size, position and maximized/fullscreen state
+    /// they were closed with, kept in `window-state.json` in the app's data dir.
+    /// Windows are moved back on screen if their monitor is gone.
+    pub fn remember_window_state(mut self) -> Self {
+        self.remember_window_state = true;
+        self
+    }
+
+    /// Number of threads route handlers run on (defaults to the number of CPUs)
+    pub fn workers(mut self, count: usize) -> Self {
+        self.workers = count.max(1);
+        self
+    }
+
+    pub fn route<H, Args>(mut self, method: &str, path: &str, handler: H) -> Self
     where
-        F: Fn(Request) -> Response + Send + Sync + 'static,
+        H: Handler<Args>,
     {
-        self.router.add(method, path, handler);
+        self.router.add(method, path, Arc::new(move |req| handler.call(req)));
         self
     }
 
+    /// Register an async handler (`async fn` or a closure returning a future),
+    /// run on the embedded Tokio runtime. Accepts the same extractors as `route`.
+    #[cfg(feature = "tokio")]
+    pub fn route_async<H, Args>(mut self, method: &str, path: &str, handler: H) -> Self
+    where
+        H: AsyncHandler<Args>,
+    {
+        self.router.add(method, path, Arc::new(move |req| handler.call(req)));
+        self
+    }
+
+    /// Register a set of routes under a shared path prefix, with their own middleware:
+    /// `app.group("/api/library", |g| g.layer(auth).route("GET", "/books", list_books))`
+    pub fn group<F>(mut self, prefix: &str, build: F) -> Self
+    where
+        F: FnOnce(Group) -> Group,
+    {
+        build(Group::new(prefix)).register(&mut self.router);
+        self
+    }
+
+    /// Wrap every route handler with a middleware. It receives the request and a
+    /// `Next` to continue the chain, and can inspect or replace the response.
+    pub fn layer<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(Request, Next<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.router.layer(Arc::new(middleware));
+        self
+    }
+
+    /// Share a value with all handlers, retrieved with `req.state::<T>()`.
+    /// Each type can only be managed once.
+    pub fn manage<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.state.insert(state);
+        self
+    }
+
+    /// Register an IPC command, called from the frontend with
+    /// `__WEBARCADE__.invoke(name, args)`. Arguments are deserialized from the
+    /// JSON `args`, and the result or error is returned to the caller's promise.
+    /// Commands run on the worker pool.
+    pub fn command<F, T, R, E>(mut self, name: &str, command: F) -> Self
+    where
+        F: Fn(T, CommandContext) -> Result<R, E> + Send + Sync + 'static,
+        T: DeserializeOwned,
+        R: Serialize + 'static,
+        E: Display,
+    {
+        self.commands.add(name, command);
+        self
+    }
+
+    /// Restrict which IPC commands the frontend may call. Denied calls are
+    /// rejected with a `forbidden` error and logged.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// How long the frontend waits for an IPC reply before rejecting (default
+    /// 5s, `Duration::ZERO` for no limit). Calls can override it with
+    /// `invoke(name, args, { timeout })`.
+    pub fn ipc_timeout(mut self, timeout: Duration) -> Self {
+        self.ipc_timeout = timeout;
+        self
+    }
+
+    /// Register an IPC command that returns raw bytes. The frontend's promise
+    /// resolves to an `ArrayBuffer`, without the cost of encoding the bytes as JSON.
+    pub fn command_binary<F, T, R, E>(mut self, name: &str, command: F) -> Self
+    where
+        F: Fn(T, CommandContext) -> Result<R, E> + Send + Sync + 'static,
+        T: DeserializeOwned,
+        R: Into<Vec<u8>> + 'static,
+        E: Display,
+    {
+        self.commands.add_binary(name, command);
+        self.types.binary.insert(name.to_string());
+        self
+    }
+
+    /// Run once the main window and its webview exist, before any events are
+    /// handled. Use it to start background work that emits to the frontend.
+    pub fn setup<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&SetupContext) + 'static,
+    {
+        self.setup = Some(Box::new(hook));
+        self
+    }
+
+    /// Run each time a window finishes loading a page
+    pub fn on_page_load<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&PageLoadContext) + 'static,
+    {
+        self.on_page_load = Some(Box::new(hook));
+        self
+    }
+
+    /// Run after the last window has closed, just before `run` returns
+    pub fn on_exit<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&ExitContext) + 'static,
+    {
+        self.on_exit = Some(Box::new(hook));
+        self
+    }
+
+    /// Decide whether a window may close when the user (or the frontend's
+    /// `close()`) asks to. Returning `CloseDecision::Close` goes on to ask the
+    /// frontend's `onCloseRequested` handlers, if it has any.
+    pub fn on_close_requested<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&CloseContext) -> CloseDecision + 'static,
+    {
+        self.on_close_requested = Some(Box::new(hook));
+        self
+    }
+
+    /// How long to wait for the frontend's `onCloseRequested` handlers before
+    /// closing the window anyway (default 5s)
+    pub fn close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// Declare the request and response types of a route for `export_bindings`.
+    /// `Req` is sent as the query string for GET/HEAD/DELETE and as a JSON body
+    /// otherwise; use `()` for none. The path must match the registered route,
+    /// including any group prefix, or `export_bindings` panics.
+    pub fn typed<Req: TsType, Res: TsType>(mut self, method: &str, path: &str) -> Self {
+        let signature = Signature { input: TypeRef::input::<Req>(), output: TypeRef::of::<Res>() };
+        self.types.routes.insert((method.to_ascii_uppercase(), path.to_string()), signature);
+        self
+    }
+
+    /// Declare the argument and result types of an IPC command for
+    /// `export_bindings`, which panics if no command has this name
+    pub fn typed_command<Args: TsType, Res: TsType>(mut self, name: &str) -> Self {
+        let signature = Signature { input: TypeRef::input::<Args>(), output: TypeRef::of::<Res>() };
+        self.types.commands.insert(name.to_string(), signature);
+        self
+    }
+
+    /// Write a TypeScript client for all routes and commands to `path`.
+    /// `run` does this instead of opening a window when the
+    /// `WEBARCADE_EXPORT_BINDINGS` environment variable is set to a path,
+    /// which is what `webarcade bindings` uses.
+    pub fn export_bindings(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let source = bindings::generate(&self.router.routes(), &self.commands.names(), &self.types);
+        std::fs::write(path, source)
+    }
+
+    /// A handle for emitting events to the frontend from any thread. Events
+    /// emitted before `run` are delivered once the event loop starts.
+    pub fn handle(&self) -> AppHandle {
+        self.handle.clone()
+    }
+
     /// Set the frontend directory (relative to the executable, read at runtime)
     pub fn frontend(mut self, path: impl Into<String>) -> Self {
         self.frontend_path = Some(path.into());
@@ -75,7 +342,23 @@ impl App {
         self
     }
 
-    pub fn run(self) {
+    /// Open the main window and run until the last window closes or
+    /// `AppHandle::exit` is called. Returns the exit code (0 unless set with
+    /// `exit`, or 1 if exporting bindings failed).
+    pub fn run(self) -> i32 {
+        if let Ok(path) = std::env::var("WEBARCADE_EXPORT_BINDINGS") {
+            return match self.export_bindings(&path) {
+                Ok(()) => {
+                    log::info!("Wrote bindings to {}", path);
+                    0
+                }
+                Err(e) => {
+                    log::error!("Failed to write bindings to {}: {}", path, e);
+                    1
+                }
+            };
+        }
+
         // Hide console window on Windows
         #[cfg(target_os = "windows")]
         {
@@ -110,129 +393,133 @@ impl App {
             }
         });
 
-        let router = Arc::new(self.router);
-        let frontend = Arc::new(frontend);
-        let frontend_embedded = self.frontend_embedded;
-
-        let event_loop: EventLoop<UserEvent> = EventLoopBuilder::with_user_event().build();
-        let proxy = event_loop.create_proxy();
-        let close_proxy = event_loop.create_proxy();
-
-        let window = WindowBuilder::new()
-            .with_title(&self.title)
-            .with_inner_size(LogicalSize::new(self.width, self.height))
-            .with_min_inner_size(LogicalSize::new(self.min_width, self.min_height))
-            .with_decorations(self.decorations)
-            .build(&event_loop)
-            .expect("Failed to create window");
-
-        let window = Arc::new(window);
-        let window_for_ipc = window.clone();
+        let mut event_loop: EventLoop<UserEvent> = EventLoopBuilder::with_user_event().build();
+        self.handle.attach(event_loop.create_proxy());
 
         let data_dir = dirs::data_local_dir()
             .unwrap_or_else(std::env::temp_dir)
-            .join(&self.title.replace(' ', "_").to_lowercase());
-
-        let mut web_context = WebContext::new(Some(data_dir));
-
-        let router_for_protocol = router.clone();
-        let frontend_for_protocol = frontend.clone();
-
-        let webview = WebViewBuilder::with_web_context(&mut web_context)
-            .with_custom_protocol("app".into(), move |_webview, request| {
-                let method = request.method().as_str();
-                let path = request.uri().path();
-                let query = request.uri().query().unwrap_or("");
-                let body = request.body();
-
-                let headers: std::collections::HashMap<String, String> = request.headers()
-                    .iter()
-                    .filter_map(|(k, v)| {
-                        Some((k.as_str().to_lowercase(), v.to_str().ok()?.to_string()))
-                    })
-                    .collect();
-
-                let (status, content_type, response_body, extra_headers) =
-                    crate::protocol::handle_request(
-                        &router_for_protocol,
-                        frontend_for_protocol.as_ref().as_ref(),
-                        frontend_embedded,
-                        method,
-                        path,
-                        query,
-                        headers,
-                        body,
-                    );
-
-                let mut builder = wry::http::Response::builder()
-                    .status(status)
-                    .header("Content-Type", &content_type)
-                    .header("Access-Control-Allow-Origin", "app://localhost");
-
-                for (k, v) in &extra_headers {
-                    builder = builder.header(k.as_str(), v.as_str());
-                }
+            .join(self.title.replace(' ', "_").to_lowercase());
 
-                builder.body(response_body.into()).unwrap()
-            })
-            .with_ipc_handler(move |message| {
-                let message_str = message.body();
+        let state = Arc::new(self.state);
+        let ctx = Context {
+            title: self.title,
+            width: self.width,
+            height: self.height,
+            min_width: self.min_width,
+            min_height: self.min_height,
+            decorations: self.decorations,
+            always_on_top: self.always_on_top,
+            resizable: self.resizable,
+            visible: self.visible,
+            skip_taskbar: self.skip_taskbar,
+            visible_on_all_workspaces: self.visible_on_all_workspaces,
+            icon: self.icon,
+            ipc_timeout: self.ipc_timeout,
+            close_timeout: self.close_timeout,
+            on_close_requested: self.on_close_requested,
+            on_page_load: self.on_page_load,
+            router: Arc::new(self.router),
+            state: state.clone(),
+            frontend: Arc::new(frontend),
+            frontend_embedded: self.frontend_embedded,
+            pool: Arc::new(ThreadPool::new(self.workers)),
+            commands: self.commands,
+            capabilities: self.capabilities,
+            in_flight: Arc::new(InFlight::default()),
+            blobs: Arc::new(Blobs::default()),
+            handle: self.handle.clone(),
+        };
+        let states = self.remember_window_state.then(|| WindowStates::load(data_dir.join("window-state.json")));
+        let mut windows = Windows::new(ctx, WebContext::new(Some(data_dir)), states);
+        windows
+            .open(&event_loop, WindowOptions::new(MAIN_WINDOW))
+            .expect("Failed to create window");
 
-                match serde_json::from_str::<IpcRequest>(message_str) {
-                    Ok(request) => {
-                        if request.command == "close" {
-                            let _ = close_proxy.send_event(UserEvent::CloseRequested);
-                            return;
-                        }
-                        let response = window::handle_ipc_command(&request, &window_for_ipc);
-                        let response_json = serde_json::to_string(&response).unwrap_or_default();
-                        let _ = proxy.send_event(UserEvent::IpcResponse(response_json));
-                    }
-                    Err(e) => {
-                        let response = IpcResponse::err(0, format!("Invalid request: {}", e));
-                        let response_json = serde_json::to_string(&response).unwrap_or_default();
-                        let _ = proxy.send_event(UserEvent::IpcResponse(response_json));
-                    }
-                }
-            })
-            .with_url("app://localhost/")
-            .with_devtools(cfg!(debug_assertions))
-            .with_initialization_script(&format!(
-                "document.addEventListener('DOMContentLoaded', () => {{ \
-                    const meta = document.createElement('meta'); \
-                    meta.httpEquiv = 'Content-Security-Policy'; \
-                    meta.content = \"default-src 'self' app: ; script-src 'self' app: 'unsafe-inline' 'unsafe-eval' blob:; style-src 'self' app: 'unsafe-inline'; img-src 'self' app: data: blob: https:; font-src 'self' app: data:; connect-src 'self' app: blob:; media-src 'self' app: blob:\"; \
-                    document.head.prepend(meta); \
-                }});"
-            ))
-            .with_initialization_script(IPC_BRIDGE_JS)
-            .build(&window)
-            .expect("Failed to create webview");
-
-        let webview = Arc::new(Mutex::new(webview));
-        let webview_for_events = webview.clone();
-
-        event_loop.run(move |event, _, control_flow| {
+        if let Some(setup) = self.setup {
+            let main = windows.window(MAIN_WINDOW).expect("Main window is open");
+            setup(&SetupContext { handle: self.handle.clone(), state: state.clone(), window: main });
+        }
+
+        let handle = self.handle;
+        let mut exit_code = 0;
+        let code = event_loop.run_return(move |event, target, control_flow| {
             *control_flow = ControlFlow::Wait;
 
             match event {
-                Event::UserEvent(UserEvent::IpcResponse(response_json)) => {
-                    if let Ok(wv) = webview_for_events.lock() {
-                        let script = format!("window.__WEBARCADE_IPC_CALLBACK__({})", response_json);
-                        let _ = wv.evaluate_script(&script);
+                Event::UserEvent(UserEvent::IpcResponse(label, response_json)) => {
+                    windows.eval(&label, &format!("window.__WEBARCADE_IPC_CALLBACK__({})", response_json));
+                }
+                Event::UserEvent(UserEvent::Emit(label, message_json)) => {
+                    let script = format!("window.__WEBARCADE_EMIT__ && window.__WEBARCADE_EMIT__({})", message_json);
+                    match label {
+                        Some(label) => windows.eval(&label, &script),
+                        None => windows.eval_all(&script),
                     }
                 }
-                Event::UserEvent(UserEvent::CloseRequested) => {
-                    *control_flow = ControlFlow::Exit;
+                Event::UserEvent(UserEvent::CreateWindow(options, caller)) => {
+                    let label = options.label.clone();
+                    let result = windows.open(target, options);
+                    match caller {
+                        Some((caller, id, nonce)) => {
+                            let response = match result {
+                                Ok(()) => IpcResponse::ok(id, label),
+                                Err(e) => IpcResponse::err(id, e),
+                            }
+                            .with_nonce(nonce);
+                            handle.send(UserEvent::IpcResponse(caller, serde_json::to_string(&response).unwrap_or_default()));
+                        }
+                        None => {
+                            if let Err(e) = result {
+                                log::error!("{}", e);
+                            }
+                        }
+                    }
+                }
+                Event::UserEvent(UserEvent::CloseWindow(label)) => {
+                    windows.close(&label);
+                }
+                Event::UserEvent(UserEvent::RequestClose(label, ask_frontend)) => {
+                    windows.request_close(&label, ask_frontend);
+                }
+                Event::UserEvent(UserEvent::CloseHandler(label, enabled)) => {
+                    windows.set_close_handler(&label, enabled);
+                }
+                Event::UserEvent(UserEvent::CloseAnswer(label, close)) => {
+                    windows.close_answered(&label, close);
+                }
+                Event::UserEvent(UserEvent::CloseTimeout(label, id)) => {
+                    windows.close_timed_out(&label, id);
+                }
+                Event::UserEvent(UserEvent::Exit(code)) => {
+                    exit_code = code;
+                    windows.close_all();
                 }
                 Event::WindowEvent {
+                    window_id,
                     event: WindowEvent::CloseRequested,
                     ..
                 } => {
-                    *control_flow = ControlFlow::Exit;
+                    if let Some(label) = windows.label(window_id).map(str::to_string) {
+                        windows.request_close(&label, true);
+                    }
+                }
+                Event::WindowEvent { window_id, event, .. } => {
+                    if matches!(event, WindowEvent::Moved(_) | WindowEvent::Resized(_)) {
+                        windows.track(window_id);
+                    }
+                    windows.forward(window_id, &event);
                 }
                 _ => {}
             }
+
+            if windows.is_empty() {
+                *control_flow = ControlFlow::ExitWithCode(exit_code);
+            }
         });
+
+        if let Some(on_exit) = self.on_exit {
+            on_exit(&ExitContext { code, state });
+        }
+        code
     }
 }
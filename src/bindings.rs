@@ -0,0 +1,498 @@
+//! TypeScript client generation for routes and IPC commands.
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Types that can be described in TypeScript. Implemented for primitives and
+/// std collections; use `ts_struct!` to define your own structs with it.
+pub trait TsType: 'static {
+    /// The type as written in TypeScript, e.g. `string` or `User[]`
+    fn ts_type() -> String;
+
+    /// Add any named declarations this type needs, e.g. `export interface User { ... }`
+    fn ts_declare(_decls: &mut Declarations) {}
+}
+
+/// Named TypeScript declarations collected while generating bindings
+#[derive(Default)]
+pub struct Declarations {
+    decls: BTreeMap<String, String>,
+}
+
+impl Declarations {
+    pub fn contains(&self, name: &str) -> bool {
+        self.decls.contains_key(name)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, decl: impl Into<String>) {
+        self.decls.insert(name.into(), decl.into());
+    }
+}
+
+/// Define a struct and its TypeScript interface together, so the two can't drift.
+/// Field names are used as-is; serde renames are not reflected.
+///
+/// ```ignore
+/// webarcade::ts_struct! {
+///     #[derive(Serialize, Deserialize)]
+///     pub struct User {
+///         pub id: u32,
+///         pub name: String,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! ts_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $($(#[$field_attr])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::TsType for $name {
+            fn ts_type() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn ts_declare(decls: &mut $crate::Declarations) {
+                if decls.contains(stringify!($name)) {
+                    return;
+                }
+                // Placeholder first, so recursive types terminate
+                decls.insert(stringify!($name), "");
+                $(<$ty as $crate::TsType>::ts_declare(decls);)*
+                let mut decl = format!("export interface {} {{\n", stringify!($name));
+                $(decl.push_str(&format!("  {}: {};\n", stringify!($field), <$ty as $crate::TsType>::ts_type()));)*
+                decl.push('}');
+                decls.insert(stringify!($name), decl);
+            }
+        }
+    };
+}
+
+macro_rules! impl_ts_primitive {
+    ($ts:literal: $($ty:ty),*) => {
+        $(
+            impl TsType for $ty {
+                fn ts_type() -> String {
+                    $ts.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_ts_primitive!("boolean": bool);
+impl_ts_primitive!("number": i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+impl_ts_primitive!("string": String, &'static str, char);
+impl_ts_primitive!("null": ());
+impl_ts_primitive!("unknown": serde_json::Value);
+
+impl<T: TsType> TsType for Option<T> {
+    fn ts_type() -> String {
+        format!("{} | null", T::ts_type())
+    }
+
+    fn ts_declare(decls: &mut Declarations) {
+        T::ts_declare(decls);
+    }
+}
+
+macro_rules! impl_ts_wrapper {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: TsType> TsType for $ty<T> {
+                fn ts_type() -> String {
+                    T::ts_type()
+                }
+
+                fn ts_declare(decls: &mut Declarations) {
+                    T::ts_declare(decls);
+                }
+            }
+        )*
+    };
+}
+
+impl_ts_wrapper!(Box, Arc);
+
+impl<T: TsType> TsType for crate::Json<T> {
+    fn ts_type() -> String {
+        T::ts_type()
+    }
+
+    fn ts_declare(decls: &mut Declarations) {
+        T::ts_declare(decls);
+    }
+}
+
+macro_rules! impl_ts_list {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: TsType> TsType for $ty<T> {
+                fn ts_type() -> String {
+                    let item = T::ts_type();
+                    if item.contains(' ') {
+                        format!("({})[]", item)
+                    } else {
+                        format!("{}[]", item)
+                    }
+                }
+
+                fn ts_declare(decls: &mut Declarations) {
+                    T::ts_declare(decls);
+                }
+            }
+        )*
+    };
+}
+
+impl_ts_list!(Vec, HashSet, BTreeSet);
+
+macro_rules! impl_ts_map {
+    ($($ty:ident),*) => {
+        $(
+            impl<K: TsType, V: TsType> TsType for $ty<K, V> {
+                fn ts_type() -> String {
+                    format!("Record<{}, {}>", K::ts_type(), V::ts_type())
+                }
+
+                fn ts_declare(decls: &mut Declarations) {
+                    K::ts_declare(decls);
+                    V::ts_declare(decls);
+                }
+            }
+        )*
+    };
+}
+
+impl_ts_map!(HashMap, BTreeMap);
+
+macro_rules! impl_ts_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: TsType),+> TsType for ($($name,)+) {
+            fn ts_type() -> String {
+                let types: Vec<String> = vec![$($name::ts_type()),+];
+                format!("[{}]", types.join(", "))
+            }
+
+            fn ts_declare(decls: &mut Declarations) {
+                $($name::ts_declare(decls);)+
+            }
+        }
+    };
+}
+
+impl_ts_tuple!(A);
+impl_ts_tuple!(A, B);
+impl_ts_tuple!(A, B, C);
+impl_ts_tuple!(A, B, C, D);
+
+/// A type captured for later rendering
+#[derive(Clone, Copy)]
+pub(crate) struct TypeRef {
+    ts_type: fn() -> String,
+    ts_declare: fn(&mut Declarations),
+}
+
+impl TypeRef {
+    /// `None` for `()`, meaning no request body or command arguments
+    pub fn input<T: TsType>() -> Option<Self> {
+        (TypeId::of::<T>() != TypeId::of::<()>()).then(Self::of::<T>)
+    }
+
+    pub fn of<T: TsType>() -> Self {
+        Self { ts_type: T::ts_type, ts_declare: T::ts_declare }
+    }
+}
+
+/// Request and response types declared with `App::typed` / `App::typed_command`
+#[derive(Clone, Copy)]
+pub(crate) struct Signature {
+    pub input: Option<TypeRef>,
+    pub output: TypeRef,
+}
+
+#[derive(Default)]
+pub(crate) struct TypeRegistry {
+    pub routes: HashMap<(String, String), Signature>,
+    pub commands: HashMap<String, Signature>,
+    /// Commands registered with `App::command_binary`, which resolve to `ArrayBuffer`
+    pub binary: HashSet<String>,
+}
+
+const HEADER: &str = r#"// Generated by webarcade. Do not edit.
+
+async function request<T>(method: string, path: string, body?: unknown): Promise<T> {
+  const init: RequestInit = { method };
+  if (body !== undefined) {
+    if (method === "GET" || method === "HEAD" || method === "DELETE") {
+      const query = new URLSearchParams();
+      for (const [key, value] of Object.entries(body ?? {})) {
+        if (value !== undefined && value !== null) query.append(key, String(value));
+      }
+      const qs = query.toString();
+      if (qs) path += "?" + qs;
+    } else {
+      init.headers = { "Content-Type": "application/json" };
+      init.body = JSON.stringify(body);
+    }
+  }
+  const res = await fetch(path, init);
+  const text = await res.text();
+  let data: any = text;
+  try { data = text ? JSON.parse(text) : undefined; } catch {}
+  if (!res.ok) throw new Error(data?.error ?? res.statusText);
+  return data as T;
+}
+
+export interface InvokeOptions {
+  timeout?: number;
+  signal?: AbortSignal;
+  onProgress?: (progress: any) => void;
+}
+
+function invoke<T>(name: string, args?: unknown, options?: InvokeOptions): Promise<T> {
+  return (window as any).__WEBARCADE__.invoke(name, args ?? null, options);
+}
+"#;
+
+/// Render a TypeScript module with a function per route and command.
+/// Routes without declared types use `unknown` for the body and result.
+///
+/// Panics if a type was declared for a route or command that doesn't exist,
+/// which is usually a typo or a missing group prefix.
+pub(crate) fn generate(routes: &[(String, String)], commands: &[String], types: &TypeRegistry) -> String {
+    let mut unmatched: Vec<String> = types
+        .routes
+        .keys()
+        .filter(|key| !routes.contains(key))
+        .map(|(method, path)| format!("route `{} {}`", method, path))
+        .chain(types.commands.keys().filter(|name| !commands.contains(name)).map(|name| format!("command '{}'", name)))
+        .collect();
+    if !unmatched.is_empty() {
+        unmatched.sort();
+        panic!("Types were declared for unregistered {}", unmatched.join(", "));
+    }
+
+    let mut decls = Declarations::default();
+    let mut api = String::new();
+    let mut names = HashSet::new();
+
+    for (method, path) in routes {
+        let signature = types.routes.get(&(method.clone(), path.clone()));
+        let output = signature.map(|s| render(s.output, &mut decls)).unwrap_or_else(|| "unknown".to_string());
+
+        let mut args = Vec::new();
+        let mut url = String::new();
+        let mut params = Vec::new();
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            url.push('/');
+            match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+                Some(name) => {
+                    let _ = write!(url, "${{encodeURIComponent(params.{})}}", name);
+                    params.push(format!("{}: string", name));
+                }
+                None => url.push_str(segment),
+            }
+        }
+        if url.is_empty() {
+            url.push('/');
+        }
+        if !params.is_empty() {
+            args.push(format!("params: {{ {} }}", params.join("; ")));
+        }
+
+        let body = match signature {
+            Some(Signature { input: Some(input), .. }) => {
+                args.push(format!("body: {}", render(*input, &mut decls)));
+                ", body"
+            }
+            Some(Signature { input: None, .. }) => "",
+            None => {
+                args.push("body?: unknown".to_string());
+                ", body"
+            }
+        };
+
+        let name = unique(&mut names, route_name(method, path));
+        let _ = writeln!(
+            api,
+            "  {}: ({}) => request<{}>(\"{}\", `{}`{}),",
+            name,
+            args.join(", "),
+            output,
+            method,
+            url,
+            body
+        );
+    }
+
+    let mut command_fns = String::new();
+    for name in commands {
+        let (args, mut output) = match types.commands.get(name) {
+            Some(signature) => {
+                let output = render(signature.output, &mut decls);
+                match signature.input {
+                    Some(input) => (format!("args: {}", render(input, &mut decls)), output),
+                    None => ("args: null = null".to_string(), output),
+                }
+            }
+            None => ("args?: unknown".to_string(), "unknown".to_string()),
+        };
+        if types.binary.contains(name) {
+            output = "ArrayBuffer".to_string();
+        }
+        let _ = writeln!(
+            command_fns,
+            "  {}: ({}, options?: InvokeOptions) => invoke<{}>({:?}, args, options),",
+            js_key(name),
+            args,
+            output,
+            name
+        );
+    }
+
+    let mut out = String::from(HEADER);
+    for decl in decls.decls.values().filter(|d| !d.is_empty()) {
+        let _ = write!(out, "\n{}\n", decl);
+    }
+    let _ = write!(out, "\nexport const api = {{\n{}}};\n", api);
+    let _ = write!(out, "\nexport const commands = {{\n{}}};\n", command_fns);
+    out
+}
+
+fn render(type_ref: TypeRef, decls: &mut Declarations) -> String {
+    (type_ref.ts_declare)(decls);
+    (type_ref.ts_type)()
+}
+
+/// `GET /api/users/:id` becomes `getApiUsersById`
+fn route_name(method: &str, path: &str) -> String {
+    let mut name = method.to_ascii_lowercase();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        match segment.strip_prefix(':').or_else(|| segment.strip_prefix('*')) {
+            Some(param) => {
+                name.push_str("By");
+                name.push_str(&pascal_case(param));
+            }
+            None => name.push_str(&pascal_case(segment)),
+        }
+    }
+    name
+}
+
+fn pascal_case(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
+
+fn unique(names: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut n = 2;
+    while !names.insert(candidate.clone()) {
+        candidate = format!("{}{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Command names are used as object keys, quoted unless they're plain identifiers
+fn js_key(name: &str) -> String {
+    let is_ident = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_ident {
+        name.to_string()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(routes: &[(&str, &str)]) -> Vec<(String, String)> {
+        routes.iter().map(|(m, p)| (m.to_string(), p.to_string())).collect()
+    }
+
+    #[test]
+    fn route_names_are_camel_case() {
+        assert_eq!(route_name("GET", "/api/users/:id"), "getApiUsersById");
+        assert_eq!(route_name("POST", "/api/user-settings"), "postApiUserSettings");
+        assert_eq!(route_name("GET", "/files/*file_path"), "getFilesByFilePath");
+        assert_eq!(route_name("DELETE", "/"), "delete");
+    }
+
+    #[test]
+    fn unique_appends_a_counter() {
+        let mut names = HashSet::new();
+        assert_eq!(unique(&mut names, "getApi".to_string()), "getApi");
+        assert_eq!(unique(&mut names, "getApi".to_string()), "getApi2");
+        assert_eq!(unique(&mut names, "getApi".to_string()), "getApi3");
+    }
+
+    #[test]
+    fn js_keys_are_quoted_unless_identifiers() {
+        assert_eq!(js_key("openFile"), "openFile");
+        assert_eq!(js_key("$_x1"), "$_x1");
+        assert_eq!(js_key("files:open"), "\"files:open\"");
+        assert_eq!(js_key("1st"), "\"1st\"");
+    }
+
+    #[test]
+    fn emits_typed_and_untyped_functions() {
+        let mut types = TypeRegistry::default();
+        types.routes.insert(
+            ("GET".to_string(), "/api/users/:id".to_string()),
+            Signature { input: None, output: TypeRef::of::<Vec<String>>() },
+        );
+        types.routes.insert(
+            ("POST".to_string(), "/api/users".to_string()),
+            Signature { input: TypeRef::input::<String>(), output: TypeRef::of::<Option<u32>>() },
+        );
+        types.commands.insert("rename".to_string(), Signature { input: None, output: TypeRef::of::<bool>() });
+        types.binary.insert("thumbnail".to_string());
+
+        let out = generate(
+            &routes(&[("GET", "/api/users/:id"), ("POST", "/api/users"), ("GET", "/api/raw")]),
+            &["rename".to_string(), "thumbnail".to_string(), "files:open".to_string()],
+            &types,
+        );
+
+        assert!(out.starts_with("// Generated by webarcade."));
+        assert!(out.contains(
+            "  getApiUsersById: (params: { id: string }) => \
+             request<string[]>(\"GET\", `/api/users/${encodeURIComponent(params.id)}`),\n"
+        ));
+        assert!(out.contains("  postApiUsers: (body: string) => request<number | null>(\"POST\", `/api/users`, body),\n"));
+        assert!(out.contains("  getApiRaw: (body?: unknown) => request<unknown>(\"GET\", `/api/raw`, body),\n"));
+        assert!(out.contains(
+            "  rename: (args: null = null, options?: InvokeOptions) => invoke<boolean>(\"rename\", args, options),\n"
+        ));
+        assert!(out.contains("  thumbnail: (args?: unknown, options?: InvokeOptions) => invoke<ArrayBuffer>("));
+        assert!(out.contains("  \"files:open\": (args?: unknown, options?: InvokeOptions) => invoke<unknown>("));
+    }
+
+    #[test]
+    #[should_panic(expected = "Types were declared for unregistered command 'renam', route `GET /users`")]
+    fn types_for_missing_routes_and_commands_panic() {
+        let mut types = TypeRegistry::default();
+        let signature = Signature { input: None, output: TypeRef::of::<()>() };
+        types.routes.insert(("GET".to_string(), "/users".to_string()), signature);
+        types.commands.insert("renam".to_string(), signature);
+        generate(&routes(&[("GET", "/api/users")]), &["rename".to_string()], &types);
+    }
+}
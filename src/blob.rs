@@ -0,0 +1,88 @@
+use crate::request::Response;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Path prefix binary IPC results are fetched from
+pub(crate) const BLOB_PATH: &str = "/__webarcade/blob/";
+
+/// Blobs nobody fetched (e.g. the call timed out) are dropped after this,
+/// within a quarter of it
+const BLOB_TTL: Duration = Duration::from_secs(60);
+
+/// Binary command results waiting to be fetched by the frontend. Each blob
+/// can be fetched once.
+#[derive(Default)]
+pub(crate) struct Blobs {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    blobs: HashMap<u64, (Instant, Vec<u8>)>,
+    /// Whether a thread is dropping expired blobs; it exits once none are left
+    sweeping: bool,
+}
+
+impl Inner {
+    fn evict_expired(&mut self) {
+        self.blobs.retain(|_, (created, _)| created.elapsed() < BLOB_TTL);
+    }
+}
+
+impl Blobs {
+    /// Store the bytes and return the path to fetch them from
+    pub fn insert(self: &Arc<Self>, bytes: Vec<u8>) -> String {
+        let Ok(mut inner) = self.inner.lock() else {
+            return String::new();
+        };
+        inner.evict_expired();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.blobs.insert(id, (Instant::now(), bytes));
+        if !inner.sweeping {
+            inner.sweeping = true;
+            self.sweep();
+        }
+        format!("{}{}", BLOB_PATH, id)
+    }
+
+    /// Drop expired blobs in the background, so large results nobody fetched
+    /// don't wait for the next insert to be freed
+    fn sweep(self: &Arc<Self>) {
+        let blobs = Arc::downgrade(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(BLOB_TTL / 4);
+            let Some(blobs) = blobs.upgrade() else {
+                return;
+            };
+            let Ok(mut inner) = blobs.inner.lock() else {
+                return;
+            };
+            inner.evict_expired();
+            if inner.blobs.is_empty() {
+                inner.sweeping = false;
+                return;
+            }
+        });
+    }
+
+    /// Serve and forget the blob at `path`, if it is a blob path
+    pub fn serve(&self, path: &str) -> Option<Response> {
+        let id = path.strip_prefix(BLOB_PATH)?;
+        let bytes = id
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| {
+                let mut inner = self.inner.lock().ok()?;
+                inner.evict_expired();
+                inner.blobs.remove(&id)
+            })
+            .map(|(_, bytes)| bytes);
+        Some(match bytes {
+            Some(bytes) => Response::bytes(bytes, "application/octet-stream").with_header("Cache-Control", "no-store"),
+            None => Response::error(404, "Blob not found"),
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+/// Origins of pages served by the `app://` protocol. Windows reports them as
+/// `http://app.localhost`, or `https://` with the HTTPS scheme enabled.
+const APP_ORIGINS: &[&str] = &["app://localhost", "http://app.localhost", "https://app.localhost"];
+
+/// Which IPC commands (built-in window commands and `App::command`s) pages may
+/// call. The app's own pages may call everything by default; `allow` switches
+/// to an allowlist, and `deny` always wins. Pages from any other origin may
+/// call nothing unless that origin has its own rules, added with `origin`.
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    allow: Option<HashSet<String>>,
+    deny: HashSet<String>,
+    origins: Vec<(String, Capabilities)>,
+}
+
+impl Capabilities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow these commands (can be called repeatedly to extend the list)
+    pub fn allow<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow.get_or_insert_with(HashSet::new).extend(commands.into_iter().map(Into::into));
+        self
+    }
+
+    /// Never allow these commands
+    pub fn deny<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.deny.extend(commands.into_iter().map(Into::into));
+        self
+    }
+
+    /// Rules for pages from `origin`, e.g. `"https://docs.example.com"`.
+    /// These replace the defaults for that origin.
+    pub fn origin(mut self, origin: impl Into<String>, capabilities: Capabilities) -> Self {
+        self.origins.push((origin.into(), capabilities));
+        self
+    }
+
+    pub fn is_allowed(&self, command: &str, origin: &str) -> bool {
+        let rules = match self.origins.iter().find(|(o, _)| o == origin) {
+            Some((_, scoped)) => scoped,
+            None if is_app_origin(origin) => self,
+            None => return false,
+        };
+        !rules.deny.contains(command) && rules.allow.as_ref().is_none_or(|allow| allow.contains(command))
+    }
+}
+
+/// Whether `origin` is one of the app's own pages
+pub(crate) fn is_app_origin(origin: &str) -> bool {
+    APP_ORIGINS.contains(&origin)
+}
@@ -16,6 +16,10 @@ fn main() {
             };
             create_project(name);
         }
+        Some("bindings") => {
+            let out = args.get(2).map(|s| s.as_str()).unwrap_or("src/bindings.ts");
+            export_bindings(out);
+        }
         Some("--version" | "-V") => {
             println!("webarcade {}", env!("CARGO_PKG_VERSION"));
         }
@@ -24,6 +28,7 @@ fn main() {
             println!();
             println!("Usage:");
             println!("  webarcade new <project-name>    Create a new project");
+            println!("  webarcade bindings [out.ts]     Generate a TypeScript client (default: src/bindings.ts)");
             println!("  webarcade --version             Show version");
         }
     }
@@ -61,11 +66,12 @@ serde_json = "1"
 r#"use webarcade::{{App, Request, Response}};
 
 fn main() {{
-    App::new("{name}", 1280, 720)
+    let code = App::new("{name}", 1280, 720)
         .min_size(800, 600)
         .route("GET", "/api/greet", handle_greet)
         .frontend("dist")
         .run();
+    std::process::exit(code);
 }}
 
 fn handle_greet(req: Request) -> Response {{
@@ -227,6 +233,25 @@ node_modules/
     println!("  npm run build && cargo run");
 }
 
+fn export_bindings(out: &str) {
+    let out = std::env::current_dir()
+        .map(|dir| dir.join(out))
+        .unwrap_or_else(|_| Path::new(out).to_path_buf());
+
+    println!("Generating bindings...");
+    let ok = Command::new("cargo")
+        .arg("run")
+        .env("WEBARCADE_EXPORT_BINDINGS", &out)
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+
+    if !ok {
+        eprintln!("Error: failed to run the app to export bindings");
+        std::process::exit(1);
+    }
+}
+
 fn write(path: impl AsRef<Path>, content: &str) {
     fs::write(&path, content).unwrap_or_else(|e| {
         eprintln!("Failed to write {}: {}", path.as_ref().display(), e);
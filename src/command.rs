@@ -0,0 +1,155 @@
+use crate::handle::{AppHandle, UserEvent};
+use crate::state::StateMap;
+use crate::window::{self, IpcResponse};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub(crate) type CommandFn = Arc<dyn Fn(serde_json::Value, CommandContext) -> Result<Output, String> + Send + Sync>;
+
+/// What a command returned: JSON, or raw bytes from `App::command_binary`
+pub(crate) enum Output {
+    Json(serde_json::Value),
+    Binary(Vec<u8>),
+}
+
+/// Passed to IPC command handlers registered with `App::command`
+pub struct CommandContext {
+    pub(crate) id: u64,
+    pub(crate) nonce: Option<String>,
+    pub(crate) window: String,
+    pub(crate) handle: AppHandle,
+    pub(crate) state: Arc<StateMap>,
+    pub(crate) cancelled: Arc<AtomicBool>,
+}
+
+impl CommandContext {
+    pub fn handle(&self) -> &AppHandle {
+        &self.handle
+    }
+
+    /// Label of the window that made the call
+    pub fn window(&self) -> &str {
+        &self.window
+    }
+
+    /// Shared state registered with `App::manage`
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+
+    /// Whether the caller gave up on this call (timed out or aborted).
+    /// Long-running commands should check this and return early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Report progress to the caller's `onProgress` callback. This also
+    /// restarts the caller's timeout.
+    pub fn progress(&self, value: impl Serialize) {
+        let response = IpcResponse::progress(self.id, value).with_nonce(self.nonce.clone());
+        self.handle.send(UserEvent::IpcResponse(self.window.clone(), serde_json::to_string(&response).unwrap_or_default()));
+    }
+}
+
+/// Cancellation flags of commands currently running, by window label, page
+/// nonce and call id. The nonce keeps a call left over from before a reload
+/// from clearing or cancelling a new call with the same id.
+#[derive(Default)]
+pub(crate) struct InFlight {
+    calls: Mutex<HashMap<CallKey, Arc<AtomicBool>>>,
+}
+
+type CallKey = (String, Option<String>, u64);
+
+impl InFlight {
+    pub fn start(&self, window: &str, nonce: Option<&str>, id: u64) -> Arc<AtomicBool> {
+        let flag = Arc::new(AtomicBool::new(false));
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.insert(key(window, nonce, id), flag.clone());
+        }
+        flag
+    }
+
+    pub fn finish(&self, window: &str, nonce: Option<&str>, id: u64) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.remove(&key(window, nonce, id));
+        }
+    }
+
+    pub fn cancel(&self, window: &str, nonce: Option<&str>, id: u64) {
+        if let Ok(calls) = self.calls.lock() {
+            if let Some(flag) = calls.get(&key(window, nonce, id)) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn key(window: &str, nonce: Option<&str>, id: u64) -> CallKey {
+    (window.to_string(), nonce.map(str::to_string), id)
+}
+
+#[derive(Default)]
+pub(crate) struct Commands {
+    commands: HashMap<String, CommandFn>,
+}
+
+impl Commands {
+    /// Panics if the name is taken by another command or a built-in window command
+    pub fn add<F, T, R, E>(&mut self, name: &str, command: F)
+    where
+        F: Fn(T, CommandContext) -> Result<R, E> + Send + Sync + 'static,
+        T: DeserializeOwned,
+        R: Serialize + 'static,
+        E: Display,
+    {
+        self.insert(name, command, |result| serde_json::to_value(result).map(Output::Json).map_err(|e| e.to_string()));
+    }
+
+    pub fn add_binary<F, T, R, E>(&mut self, name: &str, command: F)
+    where
+        F: Fn(T, CommandContext) -> Result<R, E> + Send + Sync + 'static,
+        T: DeserializeOwned,
+        R: Into<Vec<u8>> + 'static,
+        E: Display,
+    {
+        self.insert(name, command, |result| Ok(Output::Binary(result.into())));
+    }
+
+    fn insert<F, T, R, E>(&mut self, name: &str, command: F, output: fn(R) -> Result<Output, String>)
+    where
+        F: Fn(T, CommandContext) -> Result<R, E> + Send + Sync + 'static,
+        T: DeserializeOwned,
+        R: 'static,
+        E: Display,
+    {
+        if window::BUILTIN_COMMANDS.contains(&name) {
+            panic!("Command '{}' conflicts with a built-in window command", name);
+        }
+        if self.commands.contains_key(name) {
+            panic!("Command '{}' is already registered", name);
+        }
+
+        let command_name = name.to_string();
+        let wrapped: CommandFn = Arc::new(move |args, ctx| {
+            let args: T = serde_json::from_value(args)
+                .map_err(|e| format!("Invalid arguments for '{}': {}", command_name, e))?;
+            output(command(args, ctx).map_err(|e| e.to_string())?)
+        });
+        self.commands.insert(name.to_string(), wrapped);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandFn> {
+        self.commands.get(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.commands.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,240 @@
+//! Deserializes query strings and path parameters, which arrive as string
+//! maps, into typed values. Strings are parsed on demand when the target type
+//! asks for a number or bool.
+
+use serde::de::{self, value::MapDeserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+pub fn from_map<'de, T: de::Deserialize<'de>>(map: &'de HashMap<String, String>) -> Result<T, Error> {
+    T::deserialize(Map(map))
+}
+
+/// A whole map: deserializes as a struct or map, or as a single value when it
+/// has exactly one entry (e.g. `Path<u32>` for `/users/:id`). Tuples and
+/// sequences aren't supported, since the entries have no order.
+struct Map<'de>(&'de HashMap<String, String>);
+
+impl<'de> Map<'de> {
+    fn single(&self) -> Result<Value<'de>, Error> {
+        let mut values = self.0.values();
+        match (values.next(), values.next()) {
+            (Some(value), None) => Ok(Value(value)),
+            _ => Err(de::Error::custom(format!("expected a single value, found {} values", self.0.len()))),
+        }
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Map<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = self.0.iter().map(|(k, v)| (k.as_str(), Value(v)));
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+
+    /// `None` when there are no entries, e.g. `Path<Option<u32>>` on a route without parameters
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// A single string value
+struct Value<'de>(&'de str);
+
+impl<'de> IntoDeserializer<'de, Error> for Value<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(e) => Err(de::Error::custom(format!("invalid value '{}': {}", self.0, e))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn struct_fields_are_parsed_by_type() {
+        #[derive(Deserialize)]
+        struct Params {
+            id: u32,
+            name: String,
+            active: bool,
+            page: Option<u32>,
+        }
+
+        let params: Params = from_map(&map(&[("id", "7"), ("name", "ada"), ("active", "true")])).unwrap();
+        assert_eq!((params.id, params.name.as_str(), params.active, params.page), (7, "ada", true, None));
+    }
+
+    #[test]
+    fn single_entry_deserializes_as_its_value() {
+        assert_eq!(from_map::<u32>(&map(&[("id", "42")])).unwrap(), 42);
+        assert_eq!(from_map::<String>(&map(&[("name", "ada")])).unwrap(), "ada");
+
+        let err = from_map::<u32>(&map(&[("a", "1"), ("b", "2")])).unwrap_err();
+        assert_eq!(err.to_string(), "expected a single value, found 2 values");
+    }
+
+    #[test]
+    fn option_is_none_without_entries() {
+        assert_eq!(from_map::<Option<u32>>(&map(&[])).unwrap(), None);
+        assert_eq!(from_map::<Option<u32>>(&map(&[("id", "3")])).unwrap(), Some(3));
+    }
+
+    #[test]
+    fn unit_enum_variants_match_by_name() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "lowercase")]
+        enum Sort {
+            Asc,
+            Desc,
+        }
+
+        assert_eq!(from_map::<Sort>(&map(&[("sort", "desc")])).unwrap(), Sort::Desc);
+        assert!(from_map::<Sort>(&map(&[("sort", "sideways")])).is_err());
+
+        #[derive(Deserialize)]
+        struct Query {
+            sort: Sort,
+        }
+        assert_eq!(from_map::<Query>(&map(&[("sort", "asc")])).unwrap().sort, Sort::Asc);
+    }
+
+    #[test]
+    fn invalid_numbers_name_the_value() {
+        let err = from_map::<u32>(&map(&[("id", "abc")])).unwrap_err();
+        assert!(err.to_string().starts_with("invalid value 'abc'"), "{}", err);
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Params {
+            id: u8,
+        }
+        let err = from_map::<Params>(&map(&[("id", "300")])).unwrap_err();
+        assert!(err.to_string().starts_with("invalid value '300'"), "{}", err);
+    }
+
+    #[test]
+    fn tuples_are_rejected() {
+        assert!(from_map::<(u32, u32)>(&map(&[("a", "1"), ("b", "2")])).is_err());
+    }
+}
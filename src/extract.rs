@@ -0,0 +1,113 @@
+use crate::de;
+use crate::handler::Json;
+use crate::request::{Request, Response};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Handler arguments that only need to borrow the request. Any number of
+/// these can appear in a handler's argument list.
+pub trait FromRequestParts: Sized {
+    fn from_request_parts(req: &Request) -> Result<Self, Response>;
+}
+
+/// Handler arguments that consume the request, such as a `Json` body.
+/// Only the last argument of a handler may be one of these.
+pub trait FromRequest: Sized {
+    fn from_request(req: Request) -> Result<Self, Response>;
+}
+
+impl<T: FromRequestParts> FromRequest for T {
+    fn from_request(req: Request) -> Result<Self, Response> {
+        T::from_request_parts(&req)
+    }
+}
+
+impl FromRequest for Request {
+    fn from_request(req: Request) -> Result<Self, Response> {
+        Ok(req)
+    }
+}
+
+/// Query string parameters, e.g. `Query<Search>` for `?q=rust&page=2`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequestParts for Query<T> {
+    fn from_request_parts(req: &Request) -> Result<Self, Response> {
+        de::from_map(&req.query)
+            .map(Query)
+            .map_err(|e| Response::error(400, format!("Invalid query string: {}", e)))
+    }
+}
+
+/// Path parameters, as a struct with one field per `:name` segment, or the
+/// value itself when the route has a single parameter. `Option<T>` is `None`
+/// on routes without parameters. Tuples aren't supported, because parameters
+/// are matched by name; use a struct instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequestParts for Path<T> {
+    fn from_request_parts(req: &Request) -> Result<Self, Response> {
+        de::from_map(&req.params)
+            .map(Path)
+            .map_err(|e| Response::error(400, format!("Invalid path parameter: {}", e)))
+    }
+}
+
+/// All request headers, with lowercase names
+#[derive(Debug, Clone, Default)]
+pub struct Headers(pub HashMap<String, String>);
+
+impl FromRequestParts for Headers {
+    fn from_request_parts(req: &Request) -> Result<Self, Response> {
+        Ok(Headers(req.headers.clone()))
+    }
+}
+
+/// Shared state registered with `App::manage`
+#[derive(Debug)]
+pub struct State<T>(pub Arc<T>);
+
+impl<T: Send + Sync + 'static> FromRequestParts for State<T> {
+    fn from_request_parts(req: &Request) -> Result<Self, Response> {
+        req.state.get_arc::<T>().map(State).ok_or_else(|| {
+            Response::error(500, format!("State of type `{}` is not managed", std::any::type_name::<T>()))
+        })
+    }
+}
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+/// The request body as JSON. Malformed JSON is rejected with 400, and JSON
+/// that doesn't match `T` with 422.
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(req: Request) -> Result<Self, Response> {
+        serde_json::from_slice(&req.body).map(Json).map_err(|e| {
+            let status = if e.is_data() { 422 } else { 400 };
+            Response::error(status, format!("Invalid JSON body: {}", e))
+        })
+    }
+}
+
+macro_rules! impl_deref {
+    ($($ty:ident),*) => {
+        $(
+            impl<T> Deref for $ty<T> {
+                type Target = T;
+
+                fn deref(&self) -> &T {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_deref!(Query, Path, State, Json);
@@ -0,0 +1,95 @@
+#[cfg(feature = "tokio")]
+use crate::handler::AsyncHandler;
+use crate::handler::Handler;
+use crate::middleware::{Middleware, Next};
+use crate::request::{Request, Response};
+use crate::routing::{HandlerFn, Router};
+use std::sync::Arc;
+
+/// A set of routes sharing a path prefix and middleware, built with `App::group`.
+pub struct Group {
+    prefix: String,
+    routes: Vec<(String, String, HandlerFn)>,
+    layers: Vec<Middleware>,
+}
+
+impl Group {
+    pub(crate) fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            routes: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Register a route relative to the group prefix
+    pub fn route<H, Args>(mut self, method: &str, path: &str, handler: H) -> Self
+    where
+        H: Handler<Args>,
+    {
+        self.routes.push((method.to_string(), path.to_string(), Arc::new(move |req| handler.call(req))));
+        self
+    }
+
+    /// Register an async handler (`async fn` or a closure returning a future),
+    /// run on the embedded Tokio runtime. Accepts the same extractors as `route`.
+    #[cfg(feature = "tokio")]
+    pub fn route_async<H, Args>(mut self, method: &str, path: &str, handler: H) -> Self
+    where
+        H: AsyncHandler<Args>,
+    {
+        self.routes.push((method.to_string(), path.to_string(), Arc::new(move |req| handler.call(req))));
+        self
+    }
+
+    /// Wrap every route in this group (including nested groups) with a middleware.
+    /// Group layers run inside the app-wide layers, in the order they were added.
+    pub fn layer<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(Request, Next<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+
+    /// Nest a group under this group's prefix
+    pub fn group<F>(mut self, prefix: &str, build: F) -> Self
+    where
+        F: FnOnce(Group) -> Group,
+    {
+        let group = build(Group::new(prefix));
+        let prefix = group.prefix.clone();
+        for (method, path, handler) in group.into_routes() {
+            self.routes.push((method, join(&prefix, &path), handler));
+        }
+        self
+    }
+
+    pub(crate) fn register(self, router: &mut Router) {
+        let prefix = self.prefix.clone();
+        for (method, path, handler) in self.into_routes() {
+            router.add(&method, &join(&prefix, &path), handler);
+        }
+    }
+
+    /// Routes with this group's layers applied, paths still relative to the prefix
+    fn into_routes(self) -> Vec<(String, String, HandlerFn)> {
+        if self.layers.is_empty() {
+            return self.routes;
+        }
+
+        let layers: Arc<[Middleware]> = self.layers.into();
+        self.routes
+            .into_iter()
+            .map(|(method, path, handler)| {
+                let layers = layers.clone();
+                let wrapped: HandlerFn = Arc::new(move |req| Next::new(&layers, &handler).run(req));
+                (method, path, wrapped)
+            })
+            .collect()
+    }
+}
+
+fn join(prefix: &str, path: &str) -> String {
+    format!("{}/{}", prefix, path.trim_start_matches('/'))
+}
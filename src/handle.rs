@@ -0,0 +1,98 @@
+use crate::window::WindowOptions;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tao::event_loop::EventLoopProxy;
+
+#[derive(Debug)]
+pub(crate) enum UserEvent {
+    /// A reply for the window with this label
+    IpcResponse(String, String),
+    /// An event for one window, or all of them
+    Emit(Option<String>, String),
+    /// Open a window, answering IPC call `(label, id, nonce)` if set
+    CreateWindow(WindowOptions, Option<(String, u64, Option<String>)>),
+    /// Close without asking anyone
+    CloseWindow(String),
+    /// Close if the `on_close_requested` hook and, when the flag is set, the
+    /// frontend agree
+    RequestClose(String, bool),
+    /// Whether the frontend has `onCloseRequested` handlers
+    CloseHandler(String, bool),
+    /// The frontend's answer to a close request
+    CloseAnswer(String, bool),
+    /// The frontend took too long to answer close request `id`
+    CloseTimeout(String, u64),
+    /// Close every window and return this code from `App::run`
+    Exit(i32),
+}
+
+/// A cloneable handle to the running app, usable from any thread. Obtain it
+/// with `App::handle`, or as `State<AppHandle>` in a route handler.
+#[derive(Clone)]
+pub struct AppHandle {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    proxy: Option<EventLoopProxy<UserEvent>>,
+    pending: Vec<UserEvent>,
+}
+
+impl AppHandle {
+    pub(crate) fn new() -> Self {
+        Self { inner: Arc::default() }
+    }
+
+    /// Connect to the event loop, delivering anything sent before `App::run`
+    pub(crate) fn attach(&self, proxy: EventLoopProxy<UserEvent>) {
+        if let Ok(mut inner) = self.inner.lock() {
+            for event in inner.pending.drain(..) {
+                let _ = proxy.send_event(event);
+            }
+            inner.proxy = Some(proxy);
+        }
+    }
+
+    pub(crate) fn send(&self, event: UserEvent) {
+        if let Ok(mut inner) = self.inner.lock() {
+            match &inner.proxy {
+                Some(proxy) => {
+                    let _ = proxy.send_event(event);
+                }
+                None => inner.pending.push(event),
+            }
+        }
+    }
+
+    /// Send an event to every window, received with `__WEBARCADE__.on(event, cb)`
+    pub fn emit(&self, event: &str, payload: impl Serialize) {
+        let message = serde_json::json!({ "event": event, "payload": payload });
+        self.send(UserEvent::Emit(None, message.to_string()));
+    }
+
+    /// Send an event to the window with this label only
+    pub fn emit_to(&self, label: &str, event: &str, payload: impl Serialize) {
+        let message = serde_json::json!({ "event": event, "payload": payload });
+        self.send(UserEvent::Emit(Some(label.to_string()), message.to_string()));
+    }
+
+    /// Open another window. Its webview shares the app's routes, state and
+    /// storage. Labels must be unique; creating a window with a label that's
+    /// already open is logged and ignored.
+    pub fn create_window(&self, options: WindowOptions) {
+        self.send(UserEvent::CreateWindow(options, None));
+    }
+
+    /// Close all windows, without running close handlers, and make `App::run`
+    /// return `code`
+    pub fn exit(&self, code: i32) {
+        self.send(UserEvent::Exit(code));
+    }
+
+    /// Close the window with this label, without running close handlers. The
+    /// app exits when the last window closes.
+    pub fn close_window(&self, label: &str) {
+        self.send(UserEvent::CloseWindow(label.to_string()));
+    }
+}
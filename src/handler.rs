@@ -0,0 +1,202 @@
+use crate::extract::{FromRequest, FromRequestParts};
+use crate::request::{Request, Response};
+use serde::Serialize;
+use std::fmt::Display;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+
+/// Functions usable as route handlers: any `Fn` whose arguments are extractors
+/// (the last one may consume the request body) and whose return value
+/// implements `IntoResponse`. `Args` is inferred and only disambiguates impls.
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, req: Request) -> Response;
+}
+
+/// Async route handlers, registered with `App::route_async`. The future is
+/// driven to completion on the worker thread by the embedded Tokio runtime,
+/// so it doesn't need to be `Send`.
+#[cfg(feature = "tokio")]
+pub trait AsyncHandler<Args>: Send + Sync + 'static {
+    fn call(&self, req: Request) -> Response;
+}
+
+impl<F, R> Handler<()> for F
+where
+    F: Fn() -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn call(&self, _req: Request) -> Response {
+        self().into_response()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<F, Fut, R> AsyncHandler<()> for F
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R>,
+    R: IntoResponse,
+{
+    fn call(&self, _req: Request) -> Response {
+        crate::rt::runtime().block_on(self()).into_response()
+    }
+}
+
+/// Runs the extractors for a handler's arguments, returning the rejection if one fails
+macro_rules! extract {
+    ($req:ident; $($part:ident),* ; $last:ident) => {
+        $(
+            let $part = match $part::from_request_parts(&$req) {
+                Ok(value) => value,
+                Err(rejection) => return rejection,
+            };
+        )*
+        let $last = match $last::from_request($req) {
+            Ok(value) => value,
+            Err(rejection) => return rejection,
+        };
+    };
+}
+
+macro_rules! impl_handler {
+    ($($part:ident),* ; $last:ident) => {
+        impl<F, R, $($part,)* $last> Handler<($($part,)* $last,)> for F
+        where
+            F: Fn($($part,)* $last) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($part: FromRequestParts,)*
+            $last: FromRequest,
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, req: Request) -> Response {
+                extract!(req; $($part),* ; $last);
+                self($($part,)* $last).into_response()
+            }
+        }
+
+        #[cfg(feature = "tokio")]
+        impl<F, Fut, R, $($part,)* $last> AsyncHandler<($($part,)* $last,)> for F
+        where
+            F: Fn($($part,)* $last) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = R>,
+            R: IntoResponse,
+            $($part: FromRequestParts,)*
+            $last: FromRequest,
+        {
+            #[allow(non_snake_case)]
+            fn call(&self, req: Request) -> Response {
+                extract!(req; $($part),* ; $last);
+                crate::rt::runtime().block_on(self($($part,)* $last)).into_response()
+            }
+        }
+    };
+}
+
+impl_handler!(; T1);
+impl_handler!(T1; T2);
+impl_handler!(T1, T2; T3);
+impl_handler!(T1, T2, T3; T4);
+impl_handler!(T1, T2, T3, T4; T5);
+impl_handler!(T1, T2, T3, T4, T5; T6);
+impl_handler!(T1, T2, T3, T4, T5, T6; T7);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7; T8);
+
+/// Values a route handler can return. `Response` is the wire type; everything
+/// else is converted into one.
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+/// Serializes the wrapped value as a JSON response
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+/// Errors that route handlers can return, so `?` works inside handlers
+/// returning `Result<T, E>`. By default the error becomes a JSON
+/// `{ "error": "..." }` response with status 500.
+pub trait ResponseError: Display {
+    fn status(&self) -> u16 {
+        500
+    }
+
+    fn error_response(self) -> Response
+    where
+        Self: Sized,
+    {
+        Response::error(self.status(), self.to_string())
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        Response::text(self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        Response::bytes(self, "application/octet-stream")
+    }
+}
+
+impl IntoResponse for serde_json::Value {
+    fn into_response(self) -> Response {
+        Response::json(&self)
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        Response::json(&self.0)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (u16, T) {
+    fn into_response(self) -> Response {
+        self.1.into_response().with_status(self.0)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Option<T> {
+    fn into_response(self) -> Response {
+        match self {
+            Some(value) => value.into_response(),
+            None => Response::error(404, "Not found"),
+        }
+    }
+}
+
+impl<T: IntoResponse, E: ResponseError> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(e) => e.error_response(),
+        }
+    }
+}
+
+impl ResponseError for serde_json::Error {
+    fn status(&self) -> u16 {
+        400
+    }
+}
+
+impl ResponseError for std::io::Error {}
+
+impl ResponseError for String {}
+
+impl ResponseError for &'static str {}
+
+impl ResponseError for Box<dyn std::error::Error + Send + Sync> {}
@@ -4,23 +4,79 @@
     'use strict';
 
     const pendingCalls = new Map();
+    const listeners = new Map();
+    const windowListeners = new Map();
+    const closeHandlers = new Set();
     let callId = 0;
+    // Call ids restart on every page load, and commands can outlive the page
+    // that started them, so replies carry this back to tell pages apart
+    const nonce = Array.from(crypto.getRandomValues(new Uint32Array(2)), (n) => n.toString(36)).join('');
 
     const hasNativeIpc = typeof window.ipc !== 'undefined' && typeof window.ipc.postMessage === 'function';
 
     window.__WEBARCADE_IPC_CALLBACK__ = function(response) {
+        if (response.nonce !== nonce) return;
         const pending = pendingCalls.get(response.id);
-        if (pending) {
-            pendingCalls.delete(response.id);
-            if (response.success) {
-                pending.resolve(response.data);
-            } else {
-                pending.reject(new Error(response.error || 'IPC call failed'));
-            }
+        if (!pending) return;
+        if (response.progress !== undefined) {
+            pending.progress(response.progress);
+            return;
+        }
+        pending.settle();
+        if (response.success && response.blob) {
+            fetch(response.blob)
+                .then((res) => {
+                    if (!res.ok) throw new Error(`Failed to fetch binary result (${res.status})`);
+                    return res.arrayBuffer();
+                })
+                .then(pending.resolve, pending.reject);
+        } else if (response.success) {
+            pending.resolve(response.data);
+        } else {
+            const error = new Error(response.error || 'IPC call failed');
+            if (response.code) error.code = response.code;
+            pending.reject(error);
         }
     };
 
-    function ipcCall(command, args = {}) {
+    function dispatch(map, message) {
+        const callbacks = map.get(message.event);
+        if (!callbacks) return;
+        for (const callback of [...callbacks]) {
+            try {
+                callback(message.payload);
+            } catch (e) {
+                console.error(`Listener for '${message.event}' failed:`, e);
+            }
+        }
+    }
+
+    function subscribe(map, event, callback) {
+        if (!map.has(event)) map.set(event, new Set());
+        map.get(event).add(callback);
+        return () => unsubscribe(map, event, callback);
+    }
+
+    function unsubscribe(map, event, callback) {
+        const callbacks = map.get(event);
+        if (!callbacks) return;
+        if (callback) {
+            callbacks.delete(callback);
+        } else {
+            callbacks.clear();
+        }
+        if (callbacks.size === 0) map.delete(event);
+    }
+
+    window.__WEBARCADE_EMIT__ = (message) => dispatch(listeners, message);
+    window.__WEBARCADE_WINDOW_EVENT__ = (message) => dispatch(windowListeners, message);
+
+    const config = window.__WEBARCADE_CONFIG__ || {};
+    const defaultTimeout = config.ipcTimeout ?? 5000;
+
+    // options: { timeout, signal, onProgress, window }. A timeout of 0 waits forever;
+    // progress updates restart the timer.
+    function ipcCall(command, args = {}, options = {}) {
         return new Promise((resolve, reject) => {
             if (!hasNativeIpc) {
                 if (command === 'isMaximized') return resolve(false);
@@ -30,20 +86,52 @@
                 return resolve(null);
             }
 
+            const { signal, onProgress, window: target } = options;
+            const timeout = options.timeout ?? defaultTimeout;
+            if (signal && signal.aborted) {
+                return reject(signal.reason ?? new DOMException(`IPC call '${command}' aborted`, 'AbortError'));
+            }
+
             const id = ++callId;
-            pendingCalls.set(id, { resolve, reject });
+            let timer = null;
 
-            setTimeout(() => {
-                if (pendingCalls.has(id)) {
-                    pendingCalls.delete(id);
-                    reject(new Error(`IPC call '${command}' timed out`));
+            const cancel = (error) => {
+                if (!pendingCalls.has(id)) return;
+                settle();
+                ipcCallSync('cancel', { id });
+                reject(error);
+            };
+            const onAbort = () => cancel(signal.reason ?? new DOMException(`IPC call '${command}' aborted`, 'AbortError'));
+            const arm = () => {
+                clearTimeout(timer);
+                if (timeout > 0) {
+                    timer = setTimeout(() => cancel(new Error(`IPC call '${command}' timed out`)), timeout);
+                }
+            };
+            const settle = () => {
+                pendingCalls.delete(id);
+                clearTimeout(timer);
+                if (signal) signal.removeEventListener('abort', onAbort);
+            };
+            const progress = (value) => {
+                arm();
+                if (onProgress) {
+                    try {
+                        onProgress(value);
+                    } catch (e) {
+                        console.error(`Progress callback for '${command}' failed:`, e);
+                    }
                 }
-            }, 5000);
+            };
+
+            pendingCalls.set(id, { resolve, reject, settle, progress });
+            if (signal) signal.addEventListener('abort', onAbort);
+            arm();
 
             try {
-                window.ipc.postMessage(JSON.stringify({ id, command, args }));
+                window.ipc.postMessage(JSON.stringify({ id, nonce, command, args, window: target }));
             } catch (e) {
-                pendingCalls.delete(id);
+                settle();
                 reject(e);
             }
         });
@@ -52,7 +140,7 @@
     function ipcCallSync(command, args = {}) {
         if (!hasNativeIpc) return null;
         try {
-            window.ipc.postMessage(JSON.stringify({ id: ++callId, command, args }));
+            window.ipc.postMessage(JSON.stringify({ id: ++callId, nonce, command, args }));
         } catch (e) {}
         return null;
     }
@@ -74,25 +162,108 @@
         }
     });
 
+    // handler(event) can call event.preventDefault() to keep the window open, or
+    // return a promise (e.g. while saving) that resolves to false to do the same
+    function onCloseRequested(handler) {
+        closeHandlers.add(handler);
+        if (closeHandlers.size === 1) ipcCallSync('setCloseHandler', { enabled: true });
+        return () => {
+            if (closeHandlers.delete(handler) && closeHandlers.size === 0) {
+                ipcCallSync('setCloseHandler', { enabled: false });
+            }
+        };
+    }
+
+    window.__WEBARCADE_CLOSE_REQUESTED__ = async function() {
+        let prevented = false;
+        const event = { preventDefault: () => { prevented = true; } };
+        const results = [];
+        for (const handler of [...closeHandlers]) {
+            try {
+                results.push(handler(event));
+            } catch (e) {
+                console.error('Close handler failed:', e);
+            }
+        }
+        const settled = prevented ? [] : await Promise.allSettled(results);
+        const vetoed = settled.some((r) => r.status === 'fulfilled' && r.value === false);
+        ipcCallSync('closeResponse', { close: !prevented && !vetoed });
+    };
+
+    // A new page starts without handlers, whatever the previous one registered
+    ipcCallSync('setCloseHandler', { enabled: false });
+
+    // Window controls for the window labelled `label` (null for this window)
+    function windowApi(label) {
+        const call = (command, args = {}) => ipcCall(command, args, { window: label });
+        return {
+            label: label ?? config.label ?? null,
+            // { force: true } skips onCloseRequested; on_close_requested still runs
+            close: (options = {}) => call('close', { force: !!options.force }),
+            minimize: () => call('minimize'),
+            maximize: () => call('maximize'),
+            unmaximize: () => call('unmaximize'),
+            toggleMaximize: () => call('toggleMaximize'),
+            isMaximized: () => call('isMaximized'),
+            setFullscreen: (enabled = true) => call('fullscreen', { enabled }),
+            setSize: (width, height) => call('setSize', { width, height }),
+            getSize: () => call('getSize'),
+            setPosition: (x, y) => call('setPosition', { x, y }),
+            getPosition: () => call('getPosition'),
+            setMinSize: (width, height) => call('setMinSize', { width, height }),
+            setMaxSize: (width, height) => call('setMaxSize', { width, height }),
+            center: () => call('center'),
+            setTitle: (title) => call('setTitle', { title }),
+            setAlwaysOnTop: (enabled = true) => call('setAlwaysOnTop', { enabled }),
+            isAlwaysOnTop: () => call('isAlwaysOnTop'),
+            setResizable: (enabled = true) => call('setResizable', { enabled }),
+            isResizable: () => call('isResizable'),
+            setDecorations: (enabled = true) => call('setDecorations', { enabled }),
+            isDecorated: () => call('isDecorated'),
+            setSkipTaskbar: (enabled = true) => call('setSkipTaskbar', { enabled }),
+            // PNG as an ArrayBuffer, typed array or Blob; null removes the icon
+            setIcon: async (png) => call('setIcon', { png: await pngBase64(png) }),
+            // CSS cursor names: 'default', 'pointer', 'text', 'grab', 'ew-resize', ...
+            setCursorIcon: (icon) => call('setCursorIcon', { icon }),
+            setCursorVisible: (visible = true) => call('setCursorVisible', { visible }),
+            setCursorGrab: (grab = true) => call('setCursorGrab', { grab }),
+            setFocus: () => call('setFocus'),
+            isFocused: () => call('isFocused'),
+            show: () => call('show'),
+            hide: () => call('hide'),
+            isVisible: () => call('isVisible'),
+            // 'critical', 'informational', or null to stop
+            requestUserAttention: (type = 'informational') => call('requestUserAttention', { type }),
+            setVisibleOnAllWorkspaces: (enabled = true) => call('setVisibleOnAllWorkspaces', { enabled }),
+        };
+    }
+
+    function pngBase64(png) {
+        if (png == null) return Promise.resolve(null);
+        return new Promise((resolve, reject) => {
+            const reader = new FileReader();
+            reader.onload = () => resolve(reader.result.slice(reader.result.indexOf(',') + 1));
+            reader.onerror = () => reject(reader.error);
+            reader.readAsDataURL(png instanceof Blob ? png : new Blob([png]));
+        });
+    }
+
     window.__WEBARCADE__ = {
         window: {
-            close: () => ipcCall('close'),
-            minimize: () => ipcCall('minimize'),
-            maximize: () => ipcCall('maximize'),
-            unmaximize: () => ipcCall('unmaximize'),
-            toggleMaximize: () => ipcCall('toggleMaximize'),
-            isMaximized: () => ipcCall('isMaximized'),
-            setFullscreen: (enabled = true) => ipcCall('fullscreen', { enabled }),
-            setSize: (width, height) => ipcCall('setSize', { width, height }),
-            getSize: () => ipcCall('getSize'),
-            setPosition: (x, y) => ipcCall('setPosition', { x, y }),
-            getPosition: () => ipcCall('getPosition'),
-            setMinSize: (width, height) => ipcCall('setMinSize', { width, height }),
-            setMaxSize: (width, height) => ipcCall('setMaxSize', { width, height }),
-            center: () => ipcCall('center'),
-            setTitle: (title) => ipcCall('setTitle', { title }),
+            ...windowApi(null),
             startDrag: () => ipcCallSync('startDrag'),
+            onCloseRequested,
+            // resize, move, focus, blur, maximize, unmaximize, minimize, restore,
+            // scaleFactorChange, themeChange
+            on: (event, callback) => subscribe(windowListeners, event, callback),
+            off: (event, callback) => unsubscribe(windowListeners, event, callback),
+            // options: { label, url, title, width, height }; resolves to the label
+            create: (options) => ipcCall('createWindow', options),
+            get: (label) => windowApi(label),
         },
+        invoke: (name, args = null, options = {}) => ipcCall(name, args, options),
+        on: (event, callback) => subscribe(listeners, event, callback),
+        off: (event, callback) => unsubscribe(listeners, event, callback),
         isNative: hasNativeIpc,
     };
 })();
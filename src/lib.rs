@@ -1,10 +1,45 @@
 mod app;
+mod bindings;
+mod blob;
+mod capability;
+mod command;
+mod de;
+mod extract;
+mod group;
+mod handle;
+mod handler;
+mod lifecycle;
+mod middleware;
 mod request;
+#[cfg(feature = "tokio")]
+mod rt;
 mod routing;
+mod sse;
 mod protocol;
+mod webview;
 mod window;
+mod window_state;
 mod mime;
+mod pool;
+mod state;
 
 pub use app::App;
+pub use bindings::{Declarations, TsType};
+pub use capability::Capabilities;
+pub use command::CommandContext;
+pub use group::Group;
+pub use handle::AppHandle;
+pub use extract::{FromRequest, FromRequestParts, Headers, Path, Query, State};
+pub use handler::{Handler, IntoResponse, Json, ResponseError};
+pub use lifecycle::{CloseContext, CloseDecision, ExitContext, PageLoadContext, SetupContext};
+pub use middleware::Next;
 pub use request::{Request, Response};
+pub use sse::{event_channel, EventSender, EventStream};
+pub use window::WindowOptions;
+#[cfg(feature = "tokio")]
+pub use handler::AsyncHandler;
+#[cfg(feature = "tokio")]
+pub use rt::runtime;
+#[cfg(feature = "tokio")]
+pub use tokio;
 pub use include_dir;
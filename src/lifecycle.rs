@@ -0,0 +1,99 @@
+use crate::handle::AppHandle;
+use crate::state::StateMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use tao::window::Window;
+
+/// Passed to the `App::setup` hook, which runs once the main window is open
+pub struct SetupContext {
+    pub(crate) handle: AppHandle,
+    pub(crate) state: Arc<StateMap>,
+    pub(crate) window: Rc<Window>,
+}
+
+impl SetupContext {
+    pub fn handle(&self) -> &AppHandle {
+        &self.handle
+    }
+
+    /// Shared state registered with `App::manage`
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+
+    /// The main window
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+}
+
+/// Passed to the `App::on_page_load` hook
+pub struct PageLoadContext {
+    pub(crate) window: String,
+    pub(crate) url: String,
+    pub(crate) handle: AppHandle,
+}
+
+impl PageLoadContext {
+    /// Label of the window that loaded the page
+    pub fn window(&self) -> &str {
+        &self.window
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn handle(&self) -> &AppHandle {
+        &self.handle
+    }
+}
+
+/// Passed to the `App::on_exit` hook
+pub struct ExitContext {
+    pub(crate) code: i32,
+    pub(crate) state: Arc<StateMap>,
+}
+
+impl ExitContext {
+    /// The code `App::run` is about to return
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Shared state registered with `App::manage`
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+}
+
+/// Passed to the `App::on_close_requested` hook
+pub struct CloseContext {
+    pub(crate) window: String,
+    pub(crate) handle: AppHandle,
+}
+
+impl CloseContext {
+    /// Label of the window being closed
+    pub fn window(&self) -> &str {
+        &self.window
+    }
+
+    pub fn handle(&self) -> &AppHandle {
+        &self.handle
+    }
+}
+
+/// What to do about a request to close a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseDecision {
+    /// Go ahead, after asking the frontend's `onCloseRequested` handlers if any
+    Close,
+    /// Keep the window open. Close it later with `AppHandle::close_window`.
+    Prevent,
+}
+
+pub(crate) type SetupHook = Box<dyn FnOnce(&SetupContext)>;
+pub(crate) type PageLoadHook = Box<dyn Fn(&PageLoadContext)>;
+pub(crate) type CloseHook = Box<dyn Fn(&CloseContext) -> CloseDecision>;
+pub(crate) type ExitHook = Box<dyn FnOnce(&ExitContext)>;
@@ -0,0 +1,25 @@
+use crate::request::{Request, Response};
+use crate::routing::HandlerFn;
+use std::sync::Arc;
+
+pub type Middleware = Arc<dyn Fn(Request, Next<'_>) -> Response + Send + Sync>;
+
+/// The remainder of a middleware chain. Call `run` to continue to the next
+/// layer (and eventually the route handler), or return early without it.
+pub struct Next<'a> {
+    layers: &'a [Middleware],
+    handler: &'a HandlerFn,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Middleware], handler: &'a HandlerFn) -> Self {
+        Self { layers, handler }
+    }
+
+    pub fn run(self, req: Request) -> Response {
+        match self.layers.split_first() {
+            Some((layer, rest)) => layer(req, Next::new(rest, self.handler)),
+            None => (self.handler)(req),
+        }
+    }
+}
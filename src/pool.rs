@@ -0,0 +1,42 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed-size pool of threads that route handlers run on, keeping them off the UI thread
+pub(crate) struct ThreadPool {
+    sender: Sender<Job>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for i in 0..size.max(1) {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("webarcade-worker-{}", i))
+                .spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(rx) => match rx.recv() {
+                            Ok(job) => job,
+                            Err(_) => break,
+                        },
+                        Err(_) => break,
+                    };
+                    // A panicking job must not take the worker down with it
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                })
+                .expect("Failed to spawn worker thread");
+        }
+
+        Self { sender }
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        let _ = self.sender.send(Box::new(job));
+    }
+}
@@ -1,40 +1,145 @@
+use crate::middleware::Next;
 use crate::mime;
 use crate::routing::Router;
-use crate::request::Request;
-use std::collections::HashMap;
+use crate::request::{Request, Response};
+use crate::request::Body;
+use std::io::{self, Read, SeekFrom};
 use std::path::{Path, PathBuf};
 
+/// Upper bound for an open-ended range (`bytes=start-`) of a reader body
+const RANGE_CHUNK: u64 = 4 * 1024 * 1024;
+
 pub fn handle_request(
     router: &Router,
     frontend: Option<&PathBuf>,
     frontend_embedded: Option<&'static include_dir::Dir<'static>>,
-    method: &str,
-    path: &str,
-    query: &str,
-    headers: HashMap<String, String>,
-    body: &[u8],
-) -> (u16, String, Vec<u8>, Vec<(String, String)>) {
-    if let Some(handler) = router.match_route(method, path) {
-        let req = Request::from_raw(method, path, query, headers, body);
-        let response = handler(req);
-        return (response.status, response.content_type, response.body, response.headers);
+    mut req: Request,
+) -> Response {
+    if let Some(found) = router.match_path(&req.method, &req.path) {
+        let is_head = req.method == "HEAD";
+        let handler = found.handler(&req.method)
+            .or_else(|| if is_head { found.handler("GET") } else { None });
+
+        let Some(handler) = handler else {
+            let allow = found.allowed_methods().join(", ");
+            if req.method == "OPTIONS" {
+                return Response::bytes(vec![], "text/plain").with_status(204).with_header("Allow", allow);
+            }
+            return Response::error(405, format!("Method {} not allowed", req.method)).with_header("Allow", allow);
+        };
+
+        req.params = found.params;
+        let mut response = Next::new(router.layers(), handler).run(req);
+        if is_head {
+            response.body.clear();
+            response.reader = None;
+        }
+        return response;
     }
 
     // Try embedded frontend first
     if let Some(dir) = frontend_embedded {
-        if let Some((content, content_type)) = serve_embedded(dir, path) {
-            return (200, content_type.to_string(), content, vec![]);
+        if let Some((content, content_type)) = serve_embedded(dir, &req.path) {
+            return Response::bytes(content, content_type);
         }
     }
 
     // Fall back to disk
     if let Some(dir) = frontend {
-        if let Some((content, content_type)) = serve_static(dir, path) {
-            return (200, content_type.to_string(), content, vec![]);
+        if let Some((content, content_type)) = serve_static(dir, &req.path) {
+            return Response::bytes(content, content_type);
         }
     }
 
-    (404, "text/plain".to_string(), b"Not found".to_vec(), vec![])
+    Response::bytes(b"Not found".to_vec(), "text/plain").with_status(404)
+}
+
+/// wry's custom protocol responder takes the whole body at once, so a reader
+/// body is read here, on the worker thread: all of it without a `Range`
+/// header, or just the requested slice with one.
+pub fn read_body(response: &mut Response, range: Option<&str>) {
+    let Some(mut reader) = response.reader.take() else {
+        return;
+    };
+
+    match read_range(reader.as_mut(), range.and_then(parse_range)) {
+        Ok(Slice::Full(body)) => response.body = body,
+        Ok(Slice::Partial { start, total, body }) => {
+            let end = start + body.len() as u64 - 1;
+            response.status = 206;
+            response.headers.push(("Content-Range".to_string(), format!("bytes {}-{}/{}", start, end, total)));
+            response.body = body;
+        }
+        Ok(Slice::Unsatisfiable { total }) => {
+            response.status = 416;
+            response.headers.push(("Content-Range".to_string(), format!("bytes */{}", total)));
+        }
+        Err(e) => {
+            *response = Response::error(500, format!("Failed to read response body: {}", e));
+            return;
+        }
+    }
+    response.headers.push(("Accept-Ranges".to_string(), "bytes".to_string()));
+}
+
+enum Slice {
+    Full(Vec<u8>),
+    Partial { start: u64, total: u64, body: Vec<u8> },
+    Unsatisfiable { total: u64 },
+}
+
+/// A `Range: bytes=` header: `start-end`, `start-`, or `-suffix_len`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Range {
+    Bounded(u64, u64),
+    From(u64),
+    Suffix(u64),
+}
+
+fn read_range(reader: &mut dyn Body, range: Option<Range>) -> io::Result<Slice> {
+    let total = reader.seek(SeekFrom::End(0))?;
+    let Some(range) = range else {
+        let mut body = Vec::new();
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_to_end(&mut body)?;
+        return Ok(Slice::Full(body));
+    };
+
+    let Some((start, len)) = resolve(range, total) else {
+        return Ok(Slice::Unsatisfiable { total });
+    };
+    let mut body = Vec::new();
+    reader.seek(SeekFrom::Start(start))?;
+    reader.take(len).read_to_end(&mut body)?;
+    if body.is_empty() {
+        return Ok(Slice::Unsatisfiable { total });
+    }
+    Ok(Slice::Partial { start, total, body })
+}
+
+/// The `(start, len)` of a range within `total` bytes, or `None` if it's unsatisfiable
+fn resolve(range: Range, total: u64) -> Option<(u64, u64)> {
+    let (start, end) = match range {
+        Range::Bounded(start, end) => (start, end.min(total.checked_sub(1)?)),
+        Range::From(start) => (start, start.saturating_add(RANGE_CHUNK).min(total).checked_sub(1)?),
+        Range::Suffix(0) => return None,
+        Range::Suffix(len) => (total.saturating_sub(len), total.checked_sub(1)?),
+    };
+    (start < total && start <= end).then(|| (start, end - start + 1))
+}
+
+/// Parses a single `bytes=start-end`, `bytes=start-` or `bytes=-suffix` range.
+/// Anything else, including multi-part ranges, is ignored and the whole body is sent.
+fn parse_range(header: &str) -> Option<Range> {
+    let (start, end) = header.trim().strip_prefix("bytes=")?.split_once('-')?;
+    match (start.trim(), end.trim()) {
+        ("", suffix) => Some(Range::Suffix(suffix.parse().ok()?)),
+        (start, "") => Some(Range::From(start.parse().ok()?)),
+        (start, end) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            (end >= start).then_some(Range::Bounded(start, end))
+        }
+    }
 }
 
 fn serve_embedded(dir: &include_dir::Dir<'static>, path: &str) -> Option<(Vec<u8>, &'static str)> {
@@ -87,3 +192,72 @@ fn serve_static(dir: &Path, path: &str) -> Option<(Vec<u8>, &'static str)> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn respond(data: &[u8], range: Option<&str>) -> Response {
+        let mut response = Response::reader(Cursor::new(data.to_vec()), "application/octet-stream");
+        read_body(&mut response, range);
+        response
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response.headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99"), Some(Range::Bounded(0, 99)));
+        assert_eq!(parse_range("bytes=100-"), Some(Range::From(100)));
+        assert_eq!(parse_range("bytes=-500"), Some(Range::Suffix(500)));
+        assert_eq!(parse_range("bytes=5-1"), None);
+        assert_eq!(parse_range("bytes=0-1,4-5"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+    }
+
+    #[test]
+    fn without_a_range_the_whole_body_is_read() {
+        let response = respond(b"hello world", None);
+        assert_eq!((response.status, response.body.as_slice()), (200, &b"hello world"[..]));
+        assert_eq!(header(&response, "Accept-Ranges"), Some("bytes"));
+        assert_eq!(header(&response, "Content-Range"), None);
+    }
+
+    #[test]
+    fn ranges_report_the_total_length() {
+        let response = respond(b"hello world", Some("bytes=6-10"));
+        assert_eq!((response.status, response.body.as_slice()), (206, &b"world"[..]));
+        assert_eq!(header(&response, "Content-Range"), Some("bytes 6-10/11"));
+
+        let response = respond(b"hello world", Some("bytes=6-100"));
+        assert_eq!(header(&response, "Content-Range"), Some("bytes 6-10/11"));
+
+        let response = respond(b"hello world", Some("bytes=-5"));
+        assert_eq!((response.body.as_slice(), header(&response, "Content-Range")), (&b"world"[..], Some("bytes 6-10/11")));
+
+        let response = respond(b"hello world", Some("bytes=4-"));
+        assert_eq!(header(&response, "Content-Range"), Some("bytes 4-10/11"));
+    }
+
+    #[test]
+    fn open_ended_ranges_are_capped() {
+        let data = vec![0; RANGE_CHUNK as usize + 10];
+        let response = respond(&data, Some("bytes=5-"));
+        assert_eq!(response.body.len() as u64, RANGE_CHUNK);
+        assert_eq!(header(&response, "Content-Range"), Some(format!("bytes 5-{}/{}", RANGE_CHUNK + 4, data.len()).as_str()));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_get_416_with_the_length() {
+        for range in ["bytes=11-20", "bytes=11-", "bytes=-0"] {
+            let response = respond(b"hello world", Some(range));
+            assert_eq!(response.status, 416, "{}", range);
+            assert_eq!(header(&response, "Content-Range"), Some("bytes */11"));
+            assert!(response.body.is_empty());
+        }
+        assert_eq!(respond(b"", Some("bytes=-5")).status, 416);
+    }
+}
@@ -1,17 +1,45 @@
+use crate::state::StateMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Request {
     pub method: String,
     pub path: String,
     pub query: HashMap<String, String>,
+    pub params: HashMap<String, String>,
     pub headers: HashMap<String, String>,
     pub body: Vec<u8>,
+    pub(crate) state: Arc<StateMap>,
 }
 
 impl Request {
-    pub(crate) fn from_raw(method: &str, path: &str, query: &str, headers: HashMap<String, String>, body: &[u8]) -> Self {
+    /// A request with no query, params, headers, body or state, e.g. to call a
+    /// handler from a test. Set the public fields to fill in the rest, and add
+    /// state with `with_state`.
+    pub fn new(method: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            path: path.into(),
+            query: HashMap::new(),
+            params: HashMap::new(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Add a value for `req.state::<T>()` and `State<T>`, like `App::manage`
+    /// does for real requests. Panics if a value of this type was already added.
+    pub fn with_state<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        Arc::make_mut(&mut self.state).insert(value);
+        self
+    }
+
+    pub(crate) fn from_raw(method: &str, path: &str, query: &str, headers: HashMap<String, String>, body: Vec<u8>) -> Self {
         let query_params: HashMap<String, String> = if query.is_empty() {
             HashMap::new()
         } else {
@@ -26,23 +54,27 @@ impl Request {
                 .collect()
         };
 
-        Self {
-            method: method.to_string(),
-            path: path.to_string(),
-            query: query_params,
-            headers,
-            body: body.to_vec(),
-        }
+        Self { query: query_params, headers, body, ..Self::new(method, path) }
     }
 
     pub fn query(&self, key: &str) -> Option<&str> {
         self.query.get(key).map(|s| s.as_str())
     }
 
+    /// Path parameter captured by a `:name` or `*name` route segment
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(|s| s.as_str())
+    }
+
     pub fn header(&self, key: &str) -> Option<&str> {
         self.headers.get(&key.to_lowercase()).map(|s| s.as_str())
     }
 
+    /// Shared state registered with `App::manage`
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+
     pub fn json<T: for<'de> Deserialize<'de>>(&self) -> Result<T, serde_json::Error> {
         serde_json::from_slice(&self.body)
     }
@@ -52,12 +84,29 @@ impl Request {
     }
 }
 
-#[derive(Debug)]
 pub struct Response {
     pub status: u16,
     pub content_type: String,
     pub body: Vec<u8>,
     pub headers: Vec<(String, String)>,
+    pub(crate) reader: Option<Box<dyn Body>>,
+}
+
+/// A body for `Response::reader`
+pub(crate) trait Body: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Body for T {}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("content_type", &self.content_type)
+            .field("body", &self.body)
+            .field("headers", &self.headers)
+            .field("reader", &self.reader.is_some())
+            .finish()
+    }
 }
 
 impl Response {
@@ -67,6 +116,7 @@ impl Response {
             content_type: "application/json".to_string(),
             body: serde_json::to_vec(data).unwrap_or_default(),
             headers: vec![],
+            reader: None,
         }
     }
 
@@ -76,6 +126,7 @@ impl Response {
             content_type: "text/plain; charset=utf-8".to_string(),
             body: text.into().into_bytes(),
             headers: vec![],
+            reader: None,
         }
     }
 
@@ -85,6 +136,22 @@ impl Response {
             content_type: content_type.into(),
             body: data,
             headers: vec![],
+            reader: None,
+        }
+    }
+
+    /// Body read from `reader` on the worker thread rather than in the handler.
+    /// This is not streaming: the webview takes a whole body at once, so a
+    /// request without a `Range` header reads the entire reader into memory.
+    /// A `Range: bytes=...` request seeks to the start and reads only that
+    /// slice, answering `206` with the real `Content-Range`.
+    pub fn reader(reader: impl Read + Seek + Send + 'static, content_type: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            content_type: content_type.into(),
+            body: vec![],
+            headers: vec![],
+            reader: Some(Box::new(reader)),
         }
     }
 
@@ -95,6 +162,7 @@ impl Response {
             content_type: "application/json".to_string(),
             body: body.into_bytes(),
             headers: vec![],
+            reader: None,
         }
     }
 
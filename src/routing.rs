@@ -1,38 +1,322 @@
+use crate::middleware::Middleware;
 use crate::request::{Request, Response};
 use std::collections::HashMap;
 use std::sync::Arc;
 
 pub type HandlerFn = Arc<dyn Fn(Request) -> Response + Send + Sync>;
 
+struct Endpoint {
+    pattern: String,
+    handler: HandlerFn,
+}
+
+/// A segment trie node. Children are tried static first, then parameter,
+/// then wildcard, which gives static > param > wildcard precedence.
+#[derive(Default)]
+struct Node {
+    statics: HashMap<String, Node>,
+    param: Option<Capture<Box<Node>>>,
+    wildcard: Option<Capture<HashMap<String, Endpoint>>>,
+    endpoints: HashMap<String, Endpoint>,
+}
+
+/// A `:name` or `*name` edge, with the first route that introduced it (for conflict messages).
+struct Capture<T> {
+    name: String,
+    pattern: String,
+    next: T,
+}
+
 pub struct Router {
-    routes: HashMap<String, HandlerFn>,
+    root: Node,
+    layers: Vec<Middleware>,
 }
 
 impl Router {
     pub fn new() -> Self {
-        Self { routes: HashMap::new() }
+        Self { root: Node::default(), layers: Vec::new() }
+    }
+
+    /// Add a middleware that wraps every route handler. Layers run in the order they were added.
+    pub fn layer(&mut self, middleware: Middleware) {
+        self.layers.push(middleware);
+    }
+
+    pub fn layers(&self) -> &[Middleware] {
+        &self.layers
+    }
+
+    /// Register a handler. Path segments starting with `:` capture a single
+    /// segment, and a final segment starting with `*` captures the rest of the path.
+    ///
+    /// Panics if a `:` or `*` segment has no name, or if the route duplicates or
+    /// is ambiguous with an already registered one.
+    pub fn add(&mut self, method: &str, path: &str, handler: HandlerFn) {
+        let method = method.to_ascii_uppercase();
+        let pattern = format!("{} {}", method, path);
+        let parts = split_path(path);
+        let mut node = &mut self.root;
+
+        for (i, part) in parts.iter().enumerate() {
+            if (part.starts_with(':') || part.starts_with('*')) && part.len() == 1 {
+                panic!("Segment '{}' of route `{}` needs a name, e.g. '{}id'", part, pattern, part);
+            }
+            if let Some(name) = part.strip_prefix(':') {
+                let capture = node.param.get_or_insert_with(|| Capture {
+                    name: name.to_string(),
+                    pattern: pattern.clone(),
+                    next: Box::default(),
+                });
+                if capture.name != name {
+                    conflict(&pattern, &capture.pattern);
+                }
+                node = &mut capture.next;
+            } else if let Some(name) = part.strip_prefix('*') {
+                if i != parts.len() - 1 {
+                    panic!("Wildcard segment '{}' must be the last segment of route `{}`", part, pattern);
+                }
+                let capture = node.wildcard.get_or_insert_with(|| Capture {
+                    name: name.to_string(),
+                    pattern: pattern.clone(),
+                    next: HashMap::new(),
+                });
+                if capture.name != name {
+                    conflict(&pattern, &capture.pattern);
+                }
+                insert(&mut capture.next, method, pattern, handler);
+                return;
+            } else {
+                node = node.statics.entry(part.to_string()).or_default();
+            }
+        }
+
+        insert(&mut node.endpoints, method, pattern, handler);
     }
 
-    pub fn add<F>(&mut self, method: &str, path: &str, handler: F)
-    where
-        F: Fn(Request) -> Response + Send + Sync + 'static,
-    {
-        let key = make_key(method, path);
-        self.routes.insert(key, Arc::new(handler));
+    /// Every registered route as `(method, path)`, sorted by path
+    pub fn routes(&self) -> Vec<(String, String)> {
+        let mut routes = Vec::new();
+        self.root.collect(&mut routes);
+        routes.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+        routes
     }
 
-    pub fn match_route(&self, method: &str, path: &str) -> Option<&HandlerFn> {
-        let key = make_key(method, path);
-        self.routes.get(&key)
+    /// Resolve a path to the routes registered for it. The most specific path
+    /// that has a handler for `method` (or `GET`, for `HEAD`) wins. If none
+    /// does, the most specific path is returned so the caller can answer with
+    /// its allowed methods.
+    pub fn match_path(&self, method: &str, path: &str) -> Option<Match<'_>> {
+        let method = method.to_ascii_uppercase();
+        let parts = split_path(path);
+        let has_method = |endpoints: &HashMap<String, Endpoint>| {
+            endpoints.contains_key(&method) || (method == "HEAD" && endpoints.contains_key("GET"))
+        };
+        let mut params = HashMap::new();
+        let endpoints = match self.root.find(&parts, &mut params, &has_method) {
+            Some(endpoints) => endpoints,
+            None => self.root.find(&parts, &mut params, &|endpoints| !endpoints.is_empty())?,
+        };
+        Some(Match { endpoints, params })
     }
 }
 
-fn make_key(method: &str, path: &str) -> String {
-    let mut key = String::with_capacity(method.len() + 1 + path.len());
-    for c in method.chars() {
-        key.push(c.to_ascii_uppercase());
+pub struct Match<'a> {
+    endpoints: &'a HashMap<String, Endpoint>,
+    pub params: HashMap<String, String>,
+}
+
+impl<'a> Match<'a> {
+    pub fn handler(&self, method: &str) -> Option<&'a HandlerFn> {
+        self.endpoints.get(&method.to_ascii_uppercase()).map(|e| &e.handler)
+    }
+
+    /// Methods accepted on this path, including the implicit `HEAD` and `OPTIONS`.
+    pub fn allowed_methods(&self) -> Vec<String> {
+        let mut methods: Vec<String> = self.endpoints.keys().cloned().collect();
+        if self.endpoints.contains_key("GET") && !self.endpoints.contains_key("HEAD") {
+            methods.push("HEAD".to_string());
+        }
+        if !self.endpoints.contains_key("OPTIONS") {
+            methods.push("OPTIONS".to_string());
+        }
+        methods.sort();
+        methods
+    }
+}
+
+impl Node {
+    fn collect(&self, routes: &mut Vec<(String, String)>) {
+        let endpoints = self.endpoints.values()
+            .chain(self.wildcard.iter().flat_map(|w| w.next.values()));
+        for endpoint in endpoints {
+            if let Some((method, path)) = endpoint.pattern.split_once(' ') {
+                routes.push((method.to_string(), path.to_string()));
+            }
+        }
+        for child in self.statics.values() {
+            child.collect(routes);
+        }
+        if let Some(capture) = &self.param {
+            capture.next.collect(routes);
+        }
+    }
+
+    /// Depth-first search for the most specific endpoints that `accept` takes
+    fn find<'a>(
+        &'a self,
+        parts: &[&str],
+        params: &mut HashMap<String, String>,
+        accept: &dyn Fn(&HashMap<String, Endpoint>) -> bool,
+    ) -> Option<&'a HashMap<String, Endpoint>> {
+        let Some((first, rest)) = parts.split_first() else {
+            return accept(&self.endpoints).then_some(&self.endpoints);
+        };
+
+        if let Some(found) = self.statics.get(*first).and_then(|child| child.find(rest, params, accept)) {
+            return Some(found);
+        }
+
+        if let Some(capture) = &self.param {
+            if let Some(found) = capture.next.find(rest, params, accept) {
+                params.insert(capture.name.clone(), decode(first));
+                return Some(found);
+            }
+        }
+
+        let capture = self.wildcard.as_ref().filter(|capture| accept(&capture.next))?;
+        params.insert(capture.name.clone(), decode(&parts.join("/")));
+        Some(&capture.next)
+    }
+}
+
+fn insert(endpoints: &mut HashMap<String, Endpoint>, method: String, pattern: String, handler: HandlerFn) {
+    if let Some(existing) = endpoints.get(&method) {
+        conflict(&pattern, &existing.pattern);
+    }
+    endpoints.insert(method, Endpoint { pattern, handler });
+}
+
+fn conflict(new: &str, existing: &str) -> ! {
+    panic!("Route `{}` conflicts with already registered route `{}`", new, existing);
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
+
+fn decode(s: &str) -> String {
+    urlencoding::decode(s).map(|s| s.into_owned()).unwrap_or_else(|_| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(routes: &[&str]) -> Router {
+        let mut router = Router::new();
+        for route in routes {
+            let (method, path) = route.split_once(' ').unwrap();
+            router.add(method, path, Arc::new(|_| Response::text("")));
+        }
+        router
+    }
+
+    /// The pattern of the GET route that handles `path`, and its params
+    fn resolve(router: &Router, path: &str) -> Option<(String, HashMap<String, String>)> {
+        let m = router.match_path("GET", path)?;
+        let pattern = m.endpoints.get("GET")?.pattern.clone();
+        Some((pattern, m.params))
+    }
+
+    #[test]
+    fn static_beats_param_beats_wildcard() {
+        let router = router(&["GET /files/readme", "GET /files/:name", "GET /files/*rest"]);
+        assert_eq!(resolve(&router, "/files/readme").unwrap().0, "GET /files/readme");
+
+        let (pattern, params) = resolve(&router, "/files/notes").unwrap();
+        assert_eq!(pattern, "GET /files/:name");
+        assert_eq!(params["name"], "notes");
+
+        let (pattern, params) = resolve(&router, "/files/a/b%20c").unwrap();
+        assert_eq!(pattern, "GET /files/*rest");
+        assert_eq!(params["rest"], "a/b c");
+    }
+
+    #[test]
+    fn backtracks_when_a_static_branch_dead_ends() {
+        let router = router(&["GET /users/me/settings", "GET /users/:id/posts", "GET /*path"]);
+
+        let (pattern, params) = resolve(&router, "/users/me/posts").unwrap();
+        assert_eq!(pattern, "GET /users/:id/posts");
+        assert_eq!(params["id"], "me");
+
+        let (pattern, params) = resolve(&router, "/users/me/likes").unwrap();
+        assert_eq!(pattern, "GET /*path");
+        assert_eq!(params["path"], "users/me/likes");
+        assert!(!params.contains_key("id"));
+    }
+
+    #[test]
+    fn unmatched_paths_resolve_to_nothing() {
+        let router = router(&["GET /users/:id"]);
+        assert!(router.match_path("GET", "/users").is_none());
+        assert!(router.match_path("GET", "/users/1/posts").is_none());
+    }
+
+    #[test]
+    fn falls_back_to_a_less_specific_path_with_the_method() {
+        let router = router(&["GET /users/me", "POST /users/:id"]);
+
+        let m = router.match_path("POST", "/users/me").unwrap();
+        assert_eq!(m.handler("POST").map(|_| m.params["id"].as_str()), Some("me"));
+
+        let m = router.match_path("HEAD", "/users/me").unwrap();
+        assert!(m.handler("GET").is_some() && m.params.is_empty());
+
+        // No path has DELETE, so the most specific one answers with its methods
+        let m = router.match_path("DELETE", "/users/me").unwrap();
+        assert_eq!(m.allowed_methods(), ["GET", "HEAD", "OPTIONS"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with already registered route `GET /users/:id`")]
+    fn duplicate_route_panics() {
+        router(&["GET /users/:id", "GET /users/:id"]);
+    }
+
+    #[test]
+    fn same_path_with_other_method_is_not_a_conflict() {
+        let router = router(&["GET /users/:id", "POST /users/:id"]);
+        assert_eq!(router.routes().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with already registered route `GET /users/:id`")]
+    fn renamed_param_panics() {
+        router(&["GET /users/:id", "POST /users/:user_id"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with already registered route `GET /files/*path`")]
+    fn renamed_wildcard_panics() {
+        router(&["GET /files/*path", "POST /files/*rest"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the last segment")]
+    fn wildcard_not_last_panics() {
+        router(&["GET /files/*path/meta"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "needs a name")]
+    fn unnamed_param_panics() {
+        router(&["GET /users/:"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "needs a name")]
+    fn unnamed_wildcard_panics() {
+        router(&["GET /files/*"]);
     }
-    key.push(' ');
-    key.push_str(path);
-    key
 }
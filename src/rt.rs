@@ -0,0 +1,16 @@
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// The Tokio runtime that drives async route handlers, created on first use.
+/// Use it to spawn background tasks that outlive a request.
+pub fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("webarcade-async")
+            .build()
+            .expect("Failed to create Tokio runtime")
+    })
+}
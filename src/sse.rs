@@ -0,0 +1,104 @@
+use crate::request::{Request, Response};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Frames kept for clients to catch up on; older ones are dropped
+const MAX_BUFFERED: usize = 1024;
+
+/// How long the browser waits before asking again after a response without
+/// new events, in milliseconds
+const POLL_INTERVAL: u64 = 250;
+
+struct Buffer {
+    /// `(id, frame)`, oldest first
+    frames: VecDeque<(u64, String)>,
+    next_id: u64,
+}
+
+/// Create a server-sent events channel. Push events from anywhere with the
+/// `EventSender`, and serve the `EventStream` from a route with `Response::sse`.
+pub fn event_channel() -> (EventSender, EventStream) {
+    // Ids start at 1 so that a client can be handed id 0 before any event exists
+    let buffer = Arc::new(Mutex::new(Buffer { frames: VecDeque::new(), next_id: 1 }));
+    (EventSender { buffer: buffer.clone() }, EventStream { buffer })
+}
+
+#[derive(Clone)]
+pub struct EventSender {
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+impl EventSender {
+    /// Send a named event with a JSON payload (`addEventListener(event, ...)` on the frontend)
+    pub fn send(&self, event: &str, data: impl Serialize) {
+        let data = serde_json::to_string(&data).unwrap_or_default();
+        let event = event.replace(['\r', '\n'], "");
+        self.push(format!("event: {}\n{}", event, data_lines(&data)));
+    }
+
+    /// Send an unnamed event (`onmessage` on the frontend)
+    pub fn send_data(&self, data: impl AsRef<str>) {
+        self.push(data_lines(data.as_ref()));
+    }
+
+    fn push(&self, frame: String) {
+        if let Ok(mut buffer) = self.buffer.lock() {
+            if buffer.frames.len() >= MAX_BUFFERED {
+                buffer.frames.pop_front();
+            }
+            let id = buffer.next_id;
+            buffer.next_id += 1;
+            buffer.frames.push_back((id, format!("id: {}\n{}\n", id, frame)));
+        }
+    }
+}
+
+/// The receiving end of an event channel. Every connected `EventSource` gets
+/// the events sent after it connected, as long as it reconnects before they
+/// are dropped from the buffer.
+#[derive(Clone)]
+pub struct EventStream {
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+impl Response {
+    /// Serve an event stream to `new EventSource(url)`.
+    ///
+    /// The webview's custom protocol can't hold a response open, so each
+    /// response carries the events after the client's `Last-Event-ID` and the
+    /// browser reconnects for more. A client's first request only gets the
+    /// current id, so it receives the events sent after it connected.
+    /// `EventSource` sends the header and handles the reconnects on its own.
+    pub fn sse(stream: &EventStream, req: &Request) -> Response {
+        let last_id = req.header("last-event-id").and_then(|id| id.trim().parse::<u64>().ok());
+
+        let mut body = String::new();
+        let mut has_events = false;
+        if let Ok(buffer) = stream.buffer.lock() {
+            // An id from before a restart of the app counts as a first request
+            match last_id.filter(|&id| id < buffer.next_id) {
+                Some(after) => {
+                    for (_, frame) in buffer.frames.iter().filter(|(id, _)| *id > after) {
+                        body.push_str(frame);
+                        has_events = true;
+                    }
+                }
+                // An id-only event moves the client's position without firing anything
+                None => body = format!("id: {}\n\n", buffer.next_id - 1),
+            }
+        }
+
+        // Come back right away while events are flowing, otherwise after a pause
+        let retry = if has_events { 1 } else { POLL_INTERVAL };
+        Response::bytes(format!("retry: {}\n\n{}", retry, body).into_bytes(), "text/event-stream")
+            .with_header("Cache-Control", "no-cache")
+    }
+}
+
+fn data_lines(data: &str) -> String {
+    if data.is_empty() {
+        return "data: \n".to_string();
+    }
+    data.lines().map(|line| format!("data: {}\n", line)).collect()
+}
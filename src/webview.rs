@@ -0,0 +1,485 @@
+//! Builds the webviews for app windows. Every window gets its own webview,
+//! all sharing one router, state, worker pool and `WebContext`.
+
+use crate::blob::Blobs;
+use crate::capability::{self, Capabilities};
+use crate::command::{CommandContext, Commands, InFlight, Output};
+use crate::handle::{AppHandle, UserEvent};
+use crate::lifecycle::{CloseContext, CloseDecision, CloseHook, PageLoadContext, PageLoadHook};
+use crate::pool::ThreadPool;
+use crate::request::{Request, Response};
+use crate::routing::Router;
+use crate::state::StateMap;
+use crate::window::{self, IpcRequest, IpcResponse, WindowOptions};
+use crate::window_state::WindowStates;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tao::dpi::LogicalSize;
+use tao::event::WindowEvent;
+use tao::event_loop::EventLoopWindowTarget;
+use tao::window::{Icon, Theme, Window, WindowBuilder, WindowId};
+use wry::{PageLoadEvent, WebContext, WebView, WebViewBuilder};
+
+const IPC_BRIDGE_JS: &str = include_str!("ipc_bridge.js");
+
+const CSP_INIT_JS: &str = "document.addEventListener('DOMContentLoaded', () => { \
+    const meta = document.createElement('meta'); \
+    meta.httpEquiv = 'Content-Security-Policy'; \
+    meta.content = \"default-src 'self' app: ; script-src 'self' app: 'unsafe-inline' 'unsafe-eval' blob:; style-src 'self' app: 'unsafe-inline'; img-src 'self' app: data: blob: https:; font-src 'self' app: data:; connect-src 'self' app: blob:; media-src 'self' app: blob:\"; \
+    document.head.prepend(meta); \
+});";
+
+/// Label of the window `App::run` opens
+pub(crate) const MAIN_WINDOW: &str = "main";
+
+/// Everything a window's webview needs, shared by all windows
+pub(crate) struct Context {
+    pub title: String,
+    pub width: f64,
+    pub height: f64,
+    pub min_width: f64,
+    pub min_height: f64,
+    pub decorations: bool,
+    pub always_on_top: bool,
+    pub resizable: bool,
+    pub visible: bool,
+    pub skip_taskbar: bool,
+    pub visible_on_all_workspaces: bool,
+    pub icon: Option<Icon>,
+    pub ipc_timeout: Duration,
+    pub close_timeout: Duration,
+    pub on_close_requested: Option<CloseHook>,
+    pub on_page_load: Option<PageLoadHook>,
+    pub router: Arc<Router>,
+    pub state: Arc<StateMap>,
+    pub frontend: Arc<Option<PathBuf>>,
+    pub frontend_embedded: Option<&'static include_dir::Dir<'static>>,
+    pub pool: Arc<ThreadPool>,
+    pub commands: Commands,
+    pub capabilities: Capabilities,
+    pub in_flight: Arc<InFlight>,
+    pub blobs: Arc<Blobs>,
+    pub handle: AppHandle,
+}
+
+/// Open windows by label. Owned by the event loop; the window half is shared
+/// with IPC handlers so commands can target other windows.
+pub(crate) struct Windows {
+    ctx: Rc<Context>,
+    web_context: WebContext,
+    windows: Rc<RefCell<HashMap<String, Rc<Window>>>>,
+    webviews: HashMap<String, WebView>,
+    labels: HashMap<WindowId, String>,
+    /// Last seen (maximized, minimized) of each window, to notice changes
+    modes: HashMap<String, (bool, bool)>,
+    /// Windows whose frontend has `onCloseRequested` handlers
+    close_handlers: HashSet<String>,
+    /// Close requests waiting for the frontend's answer, by label
+    closing: HashMap<String, u64>,
+    next_close_id: u64,
+    states: Option<WindowStates>,
+}
+
+impl Windows {
+    pub fn new(ctx: Context, web_context: WebContext, states: Option<WindowStates>) -> Self {
+        Self {
+            ctx: Rc::new(ctx),
+            web_context,
+            windows: Rc::default(),
+            webviews: HashMap::new(),
+            labels: HashMap::new(),
+            modes: HashMap::new(),
+            close_handlers: HashSet::new(),
+            closing: HashMap::new(),
+            next_close_id: 0,
+            states,
+        }
+    }
+
+    pub fn open(&mut self, target: &EventLoopWindowTarget<UserEvent>, options: WindowOptions) -> Result<(), String> {
+        let label = options.label;
+        if self.webviews.contains_key(&label) {
+            return Err(format!("A window labelled '{}' is already open", label));
+        }
+
+        let ctx = &self.ctx;
+        let mut builder = WindowBuilder::new()
+            .with_title(options.title.as_deref().unwrap_or(&ctx.title))
+            .with_inner_size(LogicalSize::new(options.width.unwrap_or(ctx.width), options.height.unwrap_or(ctx.height)))
+            .with_min_inner_size(LogicalSize::new(ctx.min_width, ctx.min_height))
+            .with_decorations(ctx.decorations)
+            .with_always_on_top(ctx.always_on_top)
+            .with_resizable(ctx.resizable)
+            .with_visible(ctx.visible)
+            .with_visible_on_all_workspaces(ctx.visible_on_all_workspaces)
+            .with_window_icon(ctx.icon.clone());
+        if let Some(states) = &self.states {
+            builder = states.restore(&label, builder, target.available_monitors());
+        }
+        let window = builder.build(target).map_err(|e| format!("Failed to create window: {}", e))?;
+        if ctx.skip_taskbar {
+            if let Err(e) = window::set_skip_taskbar(&window, true) {
+                log::warn!("{}", e);
+            }
+        }
+
+        let url = match options.url.as_deref() {
+            Some(url) if url.contains("://") => url.to_string(),
+            Some(path) => format!("app://localhost/{}", path.trim_start_matches('/')),
+            None => "app://localhost/".to_string(),
+        };
+        let config = serde_json::json!({ "ipcTimeout": ctx.ipc_timeout.as_millis() as u64, "label": label });
+
+        let protocol_ctx = ctx.clone();
+        let ipc_ctx = ctx.clone();
+        let ipc_label = label.clone();
+        let ipc_windows = self.windows.clone();
+        let load_ctx = ctx.clone();
+        let load_label = label.clone();
+
+        let webview = WebViewBuilder::with_web_context(&mut self.web_context)
+            .with_asynchronous_custom_protocol("app".into(), move |_webview, request, responder| {
+                serve(&protocol_ctx, request, responder);
+            })
+            .with_ipc_handler(move |message| {
+                handle_ipc(&ipc_ctx, &ipc_label, &ipc_windows, message);
+            })
+            .with_on_page_load_handler(move |event, url| {
+                if let (PageLoadEvent::Finished, Some(hook)) = (event, &load_ctx.on_page_load) {
+                    hook(&PageLoadContext { window: load_label.clone(), url, handle: load_ctx.handle.clone() });
+                }
+            })
+            .with_url(&url)
+            .with_devtools(cfg!(debug_assertions))
+            .with_initialization_script(CSP_INIT_JS)
+            .with_initialization_script(&format!("window.__WEBARCADE_CONFIG__ = {};", config))
+            .with_initialization_script(IPC_BRIDGE_JS)
+            .build(&window)
+            .map_err(|e| format!("Failed to create webview: {}", e))?;
+
+        self.labels.insert(window.id(), label.clone());
+        self.modes.insert(label.clone(), (window.is_maximized(), window.is_minimized()));
+        self.windows.borrow_mut().insert(label.clone(), Rc::new(window));
+        self.webviews.insert(label, webview);
+        Ok(())
+    }
+
+    pub fn close(&mut self, label: &str) {
+        self.webviews.remove(label);
+        if let Some(window) = self.windows.borrow_mut().remove(label) {
+            if let Some(states) = &mut self.states {
+                states.save(label, &window);
+            }
+            self.labels.remove(&window.id());
+        }
+        self.modes.remove(label);
+        self.close_handlers.remove(label);
+        self.closing.remove(label);
+    }
+
+    pub fn close_all(&mut self) {
+        let labels: Vec<String> = self.webviews.keys().cloned().collect();
+        for label in labels {
+            self.close(&label);
+        }
+    }
+
+    pub fn window(&self, label: &str) -> Option<Rc<Window>> {
+        self.windows.borrow().get(label).cloned()
+    }
+
+    /// Ask the `on_close_requested` hook, then the frontend if `ask_frontend`,
+    /// whether the window may close. A frontend that doesn't answer in time is
+    /// closed anyway.
+    pub fn request_close(&mut self, label: &str, ask_frontend: bool) {
+        if !self.webviews.contains_key(label) || (ask_frontend && self.closing.contains_key(label)) {
+            return;
+        }
+        if let Some(hook) = &self.ctx.on_close_requested {
+            let ctx = CloseContext { window: label.to_string(), handle: self.ctx.handle.clone() };
+            if hook(&ctx) == CloseDecision::Prevent {
+                return;
+            }
+        }
+        if !ask_frontend || !self.close_handlers.contains(label) {
+            self.close(label);
+            return;
+        }
+
+        self.next_close_id += 1;
+        let id = self.next_close_id;
+        self.closing.insert(label.to_string(), id);
+        self.eval(label, "window.__WEBARCADE_CLOSE_REQUESTED__ && window.__WEBARCADE_CLOSE_REQUESTED__()");
+
+        let handle = self.ctx.handle.clone();
+        let label = label.to_string();
+        let timeout = self.ctx.close_timeout;
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            handle.send(UserEvent::CloseTimeout(label, id));
+        });
+    }
+
+    pub fn set_close_handler(&mut self, label: &str, enabled: bool) {
+        if enabled {
+            self.close_handlers.insert(label.to_string());
+        } else {
+            self.close_handlers.remove(label);
+        }
+    }
+
+    pub fn close_answered(&mut self, label: &str, close: bool) {
+        if self.closing.remove(label).is_some() && close {
+            self.close(label);
+        }
+    }
+
+    pub fn close_timed_out(&mut self, label: &str, id: u64) {
+        if self.closing.get(label) == Some(&id) {
+            log::warn!("Window '{}' didn't answer the close request in time, closing it", label);
+            self.close(label);
+        }
+    }
+
+    /// Note the window's new bounds after it moved or was resized
+    pub fn track(&mut self, id: WindowId) {
+        let (Some(states), Some(label)) = (&mut self.states, self.labels.get(&id)) else {
+            return;
+        };
+        if let Some(window) = self.windows.borrow().get(label) {
+            states.track(label, window);
+        }
+    }
+
+    /// Pass a window event on to the window's frontend, for
+    /// `__WEBARCADE__.window.on(event, cb)`. Maximize and minimize have no
+    /// events of their own, so they're detected by comparing with the last state.
+    pub fn forward(&mut self, id: WindowId, event: &WindowEvent) {
+        let Some(label) = self.labels.get(&id) else {
+            return;
+        };
+        let Some(window) = self.windows.borrow().get(label).cloned() else {
+            return;
+        };
+
+        let mut events = Vec::new();
+        match event {
+            WindowEvent::Resized(size) => {
+                events.push(("resize", serde_json::json!({ "width": size.width, "height": size.height })));
+            }
+            WindowEvent::Moved(pos) => events.push(("move", serde_json::json!({ "x": pos.x, "y": pos.y }))),
+            WindowEvent::Focused(true) => events.push(("focus", serde_json::Value::Null)),
+            WindowEvent::Focused(false) => events.push(("blur", serde_json::Value::Null)),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                events.push(("scaleFactorChange", serde_json::json!({ "scaleFactor": scale_factor })));
+            }
+            WindowEvent::ThemeChanged(theme) => {
+                let theme = if *theme == Theme::Dark { "dark" } else { "light" };
+                events.push(("themeChange", serde_json::json!({ "theme": theme })));
+            }
+            _ => return,
+        }
+
+        let (maximized, minimized) = (window.is_maximized(), window.is_minimized());
+        let (was_maximized, was_minimized) = self.modes.insert(label.clone(), (maximized, minimized)).unwrap_or_default();
+        if maximized != was_maximized {
+            events.push((if maximized { "maximize" } else { "unmaximize" }, serde_json::Value::Null));
+        }
+        if minimized != was_minimized {
+            events.push((if minimized { "minimize" } else { "restore" }, serde_json::Value::Null));
+        }
+
+        for (name, payload) in events {
+            let message = serde_json::json!({ "event": name, "payload": payload });
+            self.eval(label, &format!("window.__WEBARCADE_WINDOW_EVENT__ && window.__WEBARCADE_WINDOW_EVENT__({})", message));
+        }
+    }
+
+    pub fn label(&self, id: WindowId) -> Option<&str> {
+        self.labels.get(&id).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.webviews.is_empty()
+    }
+
+    pub fn eval(&self, label: &str, script: &str) {
+        if let Some(webview) = self.webviews.get(label) {
+            let _ = webview.evaluate_script(script);
+        }
+    }
+
+    pub fn eval_all(&self, script: &str) {
+        for webview in self.webviews.values() {
+            let _ = webview.evaluate_script(script);
+        }
+    }
+}
+
+/// Answer an `app://` request on the worker pool
+fn serve(ctx: &Context, request: wry::http::Request<Vec<u8>>, responder: wry::RequestAsyncResponder) {
+    let method = request.method().as_str().to_string();
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().unwrap_or("").to_string();
+
+    let headers: HashMap<String, String> = request.headers()
+        .iter()
+        .filter_map(|(k, v)| {
+            Some((k.as_str().to_lowercase(), v.to_str().ok()?.to_string()))
+        })
+        .collect();
+
+    let range = headers.get("range").cloned();
+    let mut req = Request::from_raw(&method, &path, &query, headers, request.into_body());
+    req.state = ctx.state.clone();
+
+    let router = ctx.router.clone();
+    let frontend = ctx.frontend.clone();
+    let frontend_embedded = ctx.frontend_embedded;
+    let blobs = ctx.blobs.clone();
+
+    ctx.pool.execute(move || {
+        let response = blobs.serve(&path).unwrap_or_else(|| {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut response =
+                    crate::protocol::handle_request(&router, frontend.as_ref().as_ref(), frontend_embedded, req);
+                crate::protocol::read_body(&mut response, range.as_deref());
+                response
+            }))
+            .unwrap_or_else(|_| Response::error(500, "Handler panicked"))
+        });
+
+        let mut builder = wry::http::Response::builder()
+            .status(response.status)
+            .header("Content-Type", &response.content_type)
+            .header("Access-Control-Allow-Origin", "app://localhost");
+
+        for (k, v) in &response.headers {
+            builder = builder.header(k.as_str(), v.as_str());
+        }
+
+        // An invalid status or header value from a handler fails here
+        let response = builder.body(response.body).unwrap_or_else(|e| {
+            log::error!("Invalid response for {}: {}", path, e);
+            let mut response = wry::http::Response::new(b"Invalid response".to_vec());
+            *response.status_mut() = wry::http::StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        });
+        responder.respond(response);
+    });
+}
+
+/// Dispatch an IPC message from the window labelled `label`
+fn handle_ipc(
+    ctx: &Context,
+    label: &str,
+    windows: &RefCell<HashMap<String, Rc<Window>>>,
+    message: wry::http::Request<String>,
+) {
+    let send = |response: IpcResponse| {
+        let response_json = serde_json::to_string(&response).unwrap_or_default();
+        ctx.handle.send(UserEvent::IpcResponse(label.to_string(), response_json));
+    };
+
+    let request = match serde_json::from_str::<IpcRequest>(message.body()) {
+        Ok(request) => request,
+        Err(e) => return send(IpcResponse::err(0, format!("Invalid request: {}", e))),
+    };
+    let reply = |response: IpcResponse| send(response.with_nonce(request.nonce.clone()));
+
+    // Messages from the bridge itself are always allowed for the app's own
+    // pages, but other origins need them granted like any other command
+    let origin = origin_of(message.uri());
+    let is_bridge = matches!(request.command.as_str(), "cancel" | "setCloseHandler" | "closeResponse");
+    let allowed = (is_bridge && capability::is_app_origin(&origin)) || ctx.capabilities.is_allowed(&request.command, &origin);
+    if !allowed {
+        log::warn!("Denied IPC command '{}' from {}", request.command, origin);
+        return reply(IpcResponse::forbidden(request.id, &request.command));
+    }
+
+    match request.command.as_str() {
+        "cancel" => {
+            if let Some(id) = request.args.get("id").and_then(|v| v.as_u64()) {
+                ctx.in_flight.cancel(label, request.nonce.as_deref(), id);
+            }
+            return;
+        }
+        "setCloseHandler" => {
+            let enabled = request.args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(false);
+            return ctx.handle.send(UserEvent::CloseHandler(label.to_string(), enabled));
+        }
+        "closeResponse" => {
+            let close = request.args.get("close").and_then(|v| v.as_bool()).unwrap_or(true);
+            return ctx.handle.send(UserEvent::CloseAnswer(label.to_string(), close));
+        }
+        _ => {}
+    }
+
+    if let Some(command) = ctx.commands.get(&request.command).cloned() {
+        let id = request.id;
+        let nonce = request.nonce.clone();
+        let command_ctx = CommandContext {
+            id,
+            nonce: nonce.clone(),
+            window: label.to_string(),
+            handle: ctx.handle.clone(),
+            state: ctx.state.clone(),
+            cancelled: ctx.in_flight.start(label, nonce.as_deref(), id),
+        };
+        let handle = ctx.handle.clone();
+        let in_flight = ctx.in_flight.clone();
+        let blobs = ctx.blobs.clone();
+        let label = label.to_string();
+        ctx.pool.execute(move || {
+            let response = match panic::catch_unwind(AssertUnwindSafe(|| command(request.args, command_ctx))) {
+                Ok(Ok(Output::Json(data))) => IpcResponse::ok(id, data),
+                Ok(Ok(Output::Binary(bytes))) => IpcResponse::blob(id, blobs.insert(bytes)),
+                Ok(Err(e)) => IpcResponse::err(id, e),
+                Err(_) => IpcResponse::err(id, format!("Command '{}' panicked", request.command)),
+            }
+            .with_nonce(nonce.clone());
+            in_flight.finish(&label, nonce.as_deref(), id);
+            let response_json = serde_json::to_string(&response).unwrap_or_default();
+            handle.send(UserEvent::IpcResponse(label, response_json));
+        });
+        return;
+    }
+
+    if request.command == "createWindow" {
+        match serde_json::from_value::<WindowOptions>(request.args) {
+            Ok(options) => {
+                let caller = (label.to_string(), request.id, request.nonce.clone());
+                ctx.handle.send(UserEvent::CreateWindow(options, Some(caller)))
+            }
+            Err(e) => reply(IpcResponse::err(request.id, format!("Invalid window options: {}", e))),
+        }
+        return;
+    }
+
+    let target = request.window.as_deref().unwrap_or(label);
+    if request.command == "close" {
+        let force = request.args.get("force").and_then(|v| v.as_bool()).unwrap_or(false);
+        let target = target.to_string();
+        ctx.handle.send(UserEvent::RequestClose(target, !force));
+        return reply(IpcResponse::ok_empty(request.id));
+    }
+    // Don't hold the borrow while running the command
+    let window = windows.try_borrow().ok().and_then(|windows| windows.get(target).cloned());
+    match window {
+        Some(window) => reply(window::handle_ipc_command(&request, &window)),
+        None => reply(IpcResponse::err(request.id, format!("No window labelled '{}'", target))),
+    }
+}
+
+/// `scheme://host[:port]` of the page that sent an IPC message
+fn origin_of(uri: &wry::http::Uri) -> String {
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+        _ => uri.to_string(),
+    }
+}
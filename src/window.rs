@@ -1,7 +1,59 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
 use serde::{Deserialize, Serialize};
-use tao::window::Window;
+use tao::window::{CursorIcon, Icon, UserAttentionType, Window};
 use tao::dpi::LogicalSize;
 
+/// Commands handled by the framework itself; `App::command` can't reuse these names
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    "ping", "close", "minimize", "maximize", "unmaximize", "toggleMaximize", "fullscreen",
+    "setSize", "getSize", "setPosition", "getPosition", "setMinSize", "setMaxSize",
+    "center", "setTitle", "startDrag", "isMaximized", "cancel", "createWindow",
+    "setCloseHandler", "closeResponse", "setAlwaysOnTop", "isAlwaysOnTop", "setResizable",
+    "isResizable", "setDecorations", "isDecorated", "setSkipTaskbar", "setIcon", "setCursorIcon",
+    "setCursorVisible", "setCursorGrab", "setFocus", "isFocused", "show", "hide", "isVisible",
+    "requestUserAttention", "setVisibleOnAllWorkspaces",
+];
+
+/// A window to open with `AppHandle::create_window` or
+/// `__WEBARCADE__.window.create(...)`. Unset fields use the app's settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindowOptions {
+    pub label: String,
+    /// Page to load, a path like `/settings` or a full URL (default `/`).
+    /// Pages from other origins can only call commands their origin is given
+    /// with `Capabilities::origin`.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(default)]
+    pub height: Option<f64>,
+}
+
+impl WindowOptions {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self { label: label.into(), url: None, title: None, width: None, height: None }
+    }
+
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width as f64);
+        self.height = Some(height as f64);
+        self
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct IpcResponse {
     pub id: u64,
@@ -10,6 +62,18 @@ pub struct IpcResponse {
     pub data: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Machine-readable error kind, e.g. `"forbidden"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+    /// Set on progress updates, which leave the call pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<serde_json::Value>,
+    /// Path to fetch a binary result from, resolved to an `ArrayBuffer` by the bridge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+    /// The request's `nonce`, so a reloaded page ignores replies meant for the previous one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
 }
 
 impl IpcResponse {
@@ -19,24 +83,53 @@ impl IpcResponse {
             success: true,
             data: Some(serde_json::to_value(data).unwrap_or(serde_json::Value::Null)),
             error: None,
+            code: None,
+            progress: None,
+            blob: None,
+            nonce: None,
         }
     }
 
     pub fn ok_empty(id: u64) -> Self {
-        Self { id, success: true, data: None, error: None }
+        Self { id, success: true, data: None, error: None, code: None, progress: None, blob: None, nonce: None }
     }
 
     pub fn err(id: u64, msg: impl Into<String>) -> Self {
-        Self { id, success: false, data: None, error: Some(msg.into()) }
+        Self { success: false, error: Some(msg.into()), ..Self::ok_empty(id) }
+    }
+
+    pub fn progress(id: u64, value: impl Serialize) -> Self {
+        Self { progress: Some(serde_json::to_value(value).unwrap_or(serde_json::Value::Null)), ..Self::ok_empty(id) }
+    }
+
+    pub fn blob(id: u64, path: String) -> Self {
+        Self { blob: Some(path), ..Self::ok_empty(id) }
+    }
+
+    /// A call rejected by the app's `Capabilities`
+    pub fn forbidden(id: u64, command: &str) -> Self {
+        Self { code: Some("forbidden"), ..Self::err(id, format!("Command '{}' is not allowed", command)) }
+    }
+
+    pub fn with_nonce(mut self, nonce: Option<String>) -> Self {
+        self.nonce = nonce;
+        self
     }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct IpcRequest {
     pub id: u64,
+    /// Random per page load. Call ids restart at 1 on every load, so this tells
+    /// calls from before and after a reload apart.
+    #[serde(default)]
+    pub nonce: Option<String>,
     pub command: String,
     #[serde(default)]
     pub args: serde_json::Value,
+    /// Label of the window a built-in window command applies to, if not the caller's
+    #[serde(default)]
+    pub window: Option<String>,
 }
 
 pub fn handle_ipc_command(request: &IpcRequest, window: &Window) -> IpcResponse {
@@ -144,6 +237,192 @@ pub fn handle_ipc_command(request: &IpcRequest, window: &Window) -> IpcResponse
 
         "isMaximized" => IpcResponse::ok(id, window.is_maximized()),
 
+        "setAlwaysOnTop" => {
+            window.set_always_on_top(args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true));
+            IpcResponse::ok_empty(id)
+        }
+
+        "isAlwaysOnTop" => IpcResponse::ok(id, window.is_always_on_top()),
+
+        "setResizable" => {
+            window.set_resizable(args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true));
+            IpcResponse::ok_empty(id)
+        }
+
+        "isResizable" => IpcResponse::ok(id, window.is_resizable()),
+
+        "setDecorations" => {
+            window.set_decorations(args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true));
+            IpcResponse::ok_empty(id)
+        }
+
+        "isDecorated" => IpcResponse::ok(id, window.is_decorated()),
+
+        "setSkipTaskbar" => {
+            let skip = args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true);
+            match set_skip_taskbar(window, skip) {
+                Ok(()) => IpcResponse::ok_empty(id),
+                Err(e) => IpcResponse::err(id, e),
+            }
+        }
+
+        "setIcon" => match args.get("png") {
+            None | Some(serde_json::Value::Null) => {
+                window.set_window_icon(None);
+                IpcResponse::ok_empty(id)
+            }
+            Some(png) => {
+                let icon = png
+                    .as_str()
+                    .ok_or_else(|| "expected a base64 string".to_string())
+                    .and_then(|png| BASE64_STANDARD.decode(png).map_err(|e| e.to_string()))
+                    .and_then(|png| icon_from_png(&png));
+                match icon {
+                    Ok(icon) => {
+                        window.set_window_icon(Some(icon));
+                        IpcResponse::ok_empty(id)
+                    }
+                    Err(e) => IpcResponse::err(id, format!("Invalid icon: {}", e)),
+                }
+            }
+        },
+
+        "setCursorIcon" => {
+            let name = args.get("icon").and_then(|v| v.as_str()).unwrap_or("default");
+            match cursor_icon(name) {
+                Some(icon) => {
+                    window.set_cursor_icon(icon);
+                    IpcResponse::ok_empty(id)
+                }
+                None => IpcResponse::err(id, format!("Unknown cursor: {}", name)),
+            }
+        }
+
+        "setCursorVisible" => {
+            window.set_cursor_visible(args.get("visible").and_then(|v| v.as_bool()).unwrap_or(true));
+            IpcResponse::ok_empty(id)
+        }
+
+        "setCursorGrab" => {
+            let grab = args.get("grab").and_then(|v| v.as_bool()).unwrap_or(true);
+            match window.set_cursor_grab(grab) {
+                Ok(()) => IpcResponse::ok_empty(id),
+                Err(e) => IpcResponse::err(id, e.to_string()),
+            }
+        }
+
+        "setFocus" => {
+            window.set_focus();
+            IpcResponse::ok_empty(id)
+        }
+
+        "isFocused" => IpcResponse::ok(id, window.is_focused()),
+
+        "show" => {
+            window.set_visible(true);
+            IpcResponse::ok_empty(id)
+        }
+
+        "hide" => {
+            window.set_visible(false);
+            IpcResponse::ok_empty(id)
+        }
+
+        "isVisible" => IpcResponse::ok(id, window.is_visible()),
+
+        "requestUserAttention" => {
+            let attention = match args.get("type").and_then(|v| v.as_str()) {
+                Some("critical") => Some(UserAttentionType::Critical),
+                Some("informational") => Some(UserAttentionType::Informational),
+                _ => None,
+            };
+            window.request_user_attention(attention);
+            IpcResponse::ok_empty(id)
+        }
+
+        "setVisibleOnAllWorkspaces" => {
+            window.set_visible_on_all_workspaces(args.get("enabled").and_then(|v| v.as_bool()).unwrap_or(true));
+            IpcResponse::ok_empty(id)
+        }
+
         _ => IpcResponse::err(id, format!("Unknown command: {}", request.command)),
     }
 }
+
+/// Decode a PNG into a window icon
+pub(crate) fn icon_from_png(data: &[u8]) -> Result<Icon, String> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| e.to_string())?;
+    let pixels = &buf[..info.buffer_size()];
+
+    // Palettes are expanded to RGB(A) by the transformations above
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err("unexpected indexed color".to_string()),
+    };
+    Icon::from_rgba(rgba, info.width, info.height).map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn set_skip_taskbar(window: &Window, skip: bool) -> Result<(), String> {
+    use tao::platform::windows::WindowExtWindows;
+    window.set_skip_taskbar(skip).map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn set_skip_taskbar(window: &Window, skip: bool) -> Result<(), String> {
+    use tao::platform::unix::WindowExtUnix;
+    window.set_skip_taskbar(skip).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub(crate) fn set_skip_taskbar(_window: &Window, _skip: bool) -> Result<(), String> {
+    Err("Hiding from the taskbar is not supported on this platform".to_string())
+}
+
+/// CSS cursor names, e.g. `pointer` or `ew-resize`
+fn cursor_icon(name: &str) -> Option<CursorIcon> {
+    Some(match name {
+        "default" => CursorIcon::Default,
+        "crosshair" => CursorIcon::Crosshair,
+        "pointer" => CursorIcon::Hand,
+        "move" => CursorIcon::Move,
+        "text" => CursorIcon::Text,
+        "wait" => CursorIcon::Wait,
+        "help" => CursorIcon::Help,
+        "progress" => CursorIcon::Progress,
+        "not-allowed" => CursorIcon::NotAllowed,
+        "context-menu" => CursorIcon::ContextMenu,
+        "cell" => CursorIcon::Cell,
+        "vertical-text" => CursorIcon::VerticalText,
+        "alias" => CursorIcon::Alias,
+        "copy" => CursorIcon::Copy,
+        "no-drop" => CursorIcon::NoDrop,
+        "grab" => CursorIcon::Grab,
+        "grabbing" => CursorIcon::Grabbing,
+        "all-scroll" => CursorIcon::AllScroll,
+        "zoom-in" => CursorIcon::ZoomIn,
+        "zoom-out" => CursorIcon::ZoomOut,
+        "e-resize" => CursorIcon::EResize,
+        "n-resize" => CursorIcon::NResize,
+        "ne-resize" => CursorIcon::NeResize,
+        "nw-resize" => CursorIcon::NwResize,
+        "s-resize" => CursorIcon::SResize,
+        "se-resize" => CursorIcon::SeResize,
+        "sw-resize" => CursorIcon::SwResize,
+        "w-resize" => CursorIcon::WResize,
+        "ew-resize" => CursorIcon::EwResize,
+        "ns-resize" => CursorIcon::NsResize,
+        "nesw-resize" => CursorIcon::NeswResize,
+        "nwse-resize" => CursorIcon::NwseResize,
+        "col-resize" => CursorIcon::ColResize,
+        "row-resize" => CursorIcon::RowResize,
+        _ => return None,
+    })
+}
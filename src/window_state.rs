@@ -0,0 +1,134 @@
+//! Saves window size, position and state between launches for
+//! `App::remember_window_state`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tao::dpi::{PhysicalPosition, PhysicalSize};
+use tao::monitor::MonitorHandle;
+use tao::window::{Fullscreen, Window, WindowBuilder};
+
+/// Bounds are physical pixels, and are the window's normal (not maximized or
+/// fullscreen) bounds so that un-maximizing after a restore works as expected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct WindowState {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+    fullscreen: bool,
+    monitor: Option<String>,
+}
+
+/// Saved states by window label, in `window-state.json` in the app's data dir
+pub(crate) struct WindowStates {
+    path: PathBuf,
+    states: HashMap<String, WindowState>,
+}
+
+impl WindowStates {
+    pub fn load(path: PathBuf) -> Self {
+        let states = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self { path, states }
+    }
+
+    /// Apply the saved state for `label`, moved and shrunk as needed to be
+    /// visible on the current monitors
+    pub fn restore(
+        &self,
+        label: &str,
+        builder: WindowBuilder,
+        monitors: impl Iterator<Item = MonitorHandle>,
+    ) -> WindowBuilder {
+        let Some(state) = self.states.get(label) else {
+            return builder;
+        };
+        let monitors: Vec<MonitorHandle> = monitors.collect();
+        let visible: Vec<&MonitorHandle> = monitors.iter().filter(|m| is_visible_on(state, m)).collect();
+        let monitor = visible
+            .iter()
+            .find(|m| m.name().is_some() && m.name() == state.monitor)
+            .or_else(|| visible.first())
+            .copied();
+
+        // Bounds are only recorded while the window is in its normal state
+        let has_bounds = state.width > 0 && state.height > 0;
+        let builder = match monitor.map(|m| (m, true)).or_else(|| monitors.first().map(|m| (m, false))) {
+            _ if !has_bounds => builder,
+            Some((monitor, visible)) => {
+                let (x, y, width, height) = clamp(state, monitor, visible);
+                builder.with_inner_size(PhysicalSize::new(width, height)).with_position(PhysicalPosition::new(x, y))
+            }
+            None => builder.with_inner_size(PhysicalSize::new(state.width, state.height)),
+        };
+
+        let fullscreen = state.fullscreen.then(|| Fullscreen::Borderless(monitor.cloned()));
+        builder.with_maximized(state.maximized).with_fullscreen(fullscreen)
+    }
+
+    /// Remember the window's bounds, unless it's maximized, minimized or fullscreen
+    pub fn track(&mut self, label: &str, window: &Window) {
+        if window.is_maximized() || window.is_minimized() || window.fullscreen().is_some() {
+            return;
+        }
+        let Ok(position) = window.outer_position() else {
+            return;
+        };
+        let size = window.inner_size();
+        let state = self.states.entry(label.to_string()).or_default();
+        state.x = position.x;
+        state.y = position.y;
+        state.width = size.width;
+        state.height = size.height;
+    }
+
+    /// Record the window's final state and write all states to disk
+    pub fn save(&mut self, label: &str, window: &Window) {
+        self.track(label, window);
+        // Without tracked bounds (e.g. maximized the whole time) the entry is
+        // new and its zero bounds are skipped by `restore`
+        let state = self.states.entry(label.to_string()).or_default();
+        state.maximized = window.is_maximized();
+        state.fullscreen = window.fullscreen().is_some();
+        state.monitor = window.current_monitor().and_then(|m| m.name());
+
+        let result = serde_json::to_vec_pretty(&self.states)
+            .map_err(std::io::Error::from)
+            .and_then(|data| {
+                if let Some(dir) = self.path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&self.path, data)
+            });
+        if let Err(e) = result {
+            log::warn!("Failed to save window state to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Whether the top edge of the window (where the titlebar is) is on the monitor
+fn is_visible_on(state: &WindowState, monitor: &MonitorHandle) -> bool {
+    let (pos, size) = (monitor.position(), monitor.size());
+    let right = pos.x + size.width as i32;
+    let bottom = pos.y + size.height as i32;
+    state.x < right && state.x + state.width as i32 > pos.x && state.y >= pos.y && state.y < bottom
+}
+
+/// Fit the saved bounds inside the monitor. Windows that weren't visible on
+/// it at all are centered instead.
+fn clamp(state: &WindowState, monitor: &MonitorHandle, visible: bool) -> (i32, i32, u32, u32) {
+    let (pos, size) = (monitor.position(), monitor.size());
+    let width = state.width.min(size.width);
+    let height = state.height.min(size.height);
+    let max_x = pos.x + (size.width - width) as i32;
+    let max_y = pos.y + (size.height - height) as i32;
+    if visible {
+        (state.x.clamp(pos.x, max_x), state.y.clamp(pos.y, max_y), width, height)
+    } else {
+        (pos.x + (size.width - width) as i32 / 2, pos.y + (size.height - height) as i32 / 2, width, height)
+    }
+}